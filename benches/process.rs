@@ -0,0 +1,77 @@
+use std::fs;
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use paranoid_space::{
+    process_css, process_html, process_js, process_json, process_json5, process_markdown, process_php,
+    process_rust, spacing, spacing_stream, unspacing,
+};
+
+type Process = fn(&str) -> anyhow::Result<String>;
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(format!("test-data/{}", name)).unwrap()
+}
+
+fn formats(c: &mut Criterion) {
+    let formats: [(&str, &str, Process); 8] = [
+        ("html", "source.html", process_html),
+        ("markdown", "test.md", process_markdown),
+        ("css", "source.css", process_css),
+        ("js", "source.js", process_js),
+        ("rust", "source.rs", process_rust),
+        ("json", "data.json", process_json),
+        ("json5", "data.json", process_json5),
+        ("php", "source.php", process_php),
+    ];
+    let mut group = c.benchmark_group("process");
+    let text = fixture("test.txt");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.bench_with_input(BenchmarkId::from_parameter("text"), &text, |b, text| {
+        b.iter(|| spacing(black_box(text)))
+    });
+    for (name, file, process) in formats {
+        let input = fixture(file);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &input, |b, input| {
+            b.iter(|| process(black_box(input)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Large inputs, where the cost per byte shows
+fn large(c: &mut Criterion) {
+    const SIZE: usize = 1 << 20;
+    let repeat = |text: &str| text.repeat(SIZE / text.len() + 1);
+    let mixed = repeat(&fixture("test.txt"));
+    let spaced = spacing(&mixed).into_owned();
+    let ascii = repeat("The quick brown fox jumps over the lazy dog, see https://example.com/path.\n");
+    let cjk = repeat("当你凝视着深渊的时候，深渊也在凝视着你。");
+
+    let mut group = c.benchmark_group("large");
+    group.sample_size(20);
+    for (name, input) in [("mixed", &mixed), ("spaced", &spaced), ("ascii", &ascii), ("cjk", &cjk)] {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("spacing", name), input, |b, input| {
+            b.iter(|| spacing(black_box(input)))
+        });
+    }
+    group.throughput(Throughput::Bytes(mixed.len() as u64));
+    group.bench_with_input(BenchmarkId::new("stream", "mixed"), &mixed, |b, input| {
+        b.iter(|| spacing_stream(black_box(input.as_bytes()), std::io::sink()).unwrap())
+    });
+    group.throughput(Throughput::Bytes(spaced.len() as u64));
+    group.bench_with_input(BenchmarkId::new("unspacing", "spaced"), &spaced, |b, input| {
+        b.iter(|| unspacing(black_box(input)))
+    });
+    let markdown = repeat(&fixture("test.md"));
+    group.throughput(Throughput::Bytes(markdown.len() as u64));
+    group.bench_with_input(BenchmarkId::new("process", "markdown"), &markdown, |b, input| {
+        b.iter(|| process_markdown(black_box(input)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, formats, large);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+use unicode_properties::{GeneralCategory, UnicodeEmoji, UnicodeGeneralCategory};
+use unicode_script::{Script, UnicodeScript};
+use unicode_width::UnicodeWidthChar;
+
+/// 字符类别，由 Unicode 文字（Script）、东亚宽度（East Asian Width）等属性决定
+///
+/// 空格只插在中日韩一侧（[`CharClass::is_cjk`]）与西文一侧（[`CharClass::is_western`]）之间，
+/// 表情和宽度不定的标点不属于任何一侧，两边都不加空格。
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::CharClass;
+///
+/// assert_eq!(CharClass::of('中'), CharClass::Han);
+/// assert_eq!(CharClass::of('Ａ'), CharClass::Letter);
+/// assert!(CharClass::of('，').is_cjk());
+/// assert!(!CharClass::of('😀').is_western());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharClass {
+    /// 汉字
+    Han,
+    /// 平假名、片假名（含半角片假名）、长音符 `ー` 和浊音符
+    Kana,
+    /// 谚文
+    Hangul,
+    /// 注音符号
+    Bopomofo,
+    /// 中日韩标点和全角符号，如 `，`、`「`、`￥`，以及半角日文标点 `｡｢｣､･`
+    CjkPunctuation,
+    /// 拉丁、希腊、西里尔等文字的字母，包括全角拉丁字母 `Ａ`
+    Letter,
+    /// 数字，包括全角数字 `１`
+    Digit,
+    /// 半角标点和符号，以及货币符号
+    Symbol,
+    /// 宽度随语境变化的标点和符号，如 `—`、`…`、`“`、`°`
+    Ambiguous,
+    /// 表情符号及其组合字符
+    Emoji,
+    /// 空白和控制字符，全角空格除外
+    Space,
+}
+
+impl CharClass {
+    pub fn of(c: char) -> Self {
+        // The most common characters skip the Unicode property lookups
+        match c {
+            'a'..='z' | 'A'..='Z' => CharClass::Letter,
+            '0'..='9' => CharClass::Digit,
+            '!'..='~' => CharClass::Symbol,
+            '\0'..='\x7f' => CharClass::Space,
+            '\u{4E00}'..='\u{9FFF}' => CharClass::Han,
+            '\u{3041}'..='\u{3096}' | '\u{3099}'..='\u{309F}' | '\u{30A1}'..='\u{30FA}' | '\u{30FC}'..='\u{30FF}' => {
+                CharClass::Kana
+            }
+            '\u{AC00}'..='\u{D7A3}' => CharClass::Hangul,
+            '、' | '。' | '〈'..='】' | '，' | '！' | '？' | '：' | '；' | '（' | '）' => CharClass::CjkPunctuation,
+            _ => Self::of_unicode(c),
+        }
+    }
+
+    fn of_unicode(c: char) -> Self {
+        // The ideographic space is a full-width character of CJK text
+        if c == '\u{3000}' {
+            return CharClass::CjkPunctuation;
+        }
+        if c.is_whitespace() || c.is_control() {
+            return CharClass::Space;
+        }
+        if !c.is_ascii() && c.is_emoji_char_or_emoji_component() && !is_cjk_block(c) {
+            return CharClass::Emoji;
+        }
+        match c.script() {
+            Script::Han => return CharClass::Han,
+            Script::Hiragana | Script::Katakana => return CharClass::Kana,
+            Script::Hangul => return CharClass::Hangul,
+            Script::Bopomofo => return CharClass::Bopomofo,
+            _ => {}
+        }
+        match c {
+            // Prolonged sound marks and (semi-)voiced sound marks belong to no
+            // script but are only used in kana
+            'ー' | 'ｰ' | '\u{3099}'..='\u{309C}' | 'ﾞ' | 'ﾟ' => return CharClass::Kana,
+            // Halfwidth CJK punctuation: `｡｢｣､･`
+            '\u{FF61}'..='\u{FF65}' => return CharClass::CjkPunctuation,
+            _ => {}
+        }
+        if c.is_numeric() {
+            return CharClass::Digit;
+        }
+        if c.is_alphabetic() {
+            return CharClass::Letter;
+        }
+        if c.general_category() == GeneralCategory::CurrencySymbol && c.width() == Some(1) {
+            return CharClass::Symbol;
+        }
+        match (c.width(), c.width_cjk()) {
+            (Some(2), _) => CharClass::CjkPunctuation,
+            (narrow, cjk) if narrow != cjk => CharClass::Ambiguous,
+            _ => CharClass::Symbol,
+        }
+    }
+
+    /// 中日韩文字和标点
+    pub fn is_cjk(&self) -> bool {
+        matches!(
+            self,
+            CharClass::Han
+                | CharClass::Kana
+                | CharClass::Hangul
+                | CharClass::Bopomofo
+                | CharClass::CjkPunctuation
+        )
+    }
+
+    /// 西文字母、数字、半角符号和空白
+    pub fn is_western(&self) -> bool {
+        matches!(
+            self,
+            CharClass::Letter | CharClass::Digit | CharClass::Symbol | CharClass::Space
+        )
+    }
+}
+
+/// CJK Symbols and Punctuation and Enclosed CJK Letters, which include a few
+/// emoji such as `〽` and `㊗` that read as CJK punctuation.
+fn is_cjk_block(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303F}' | '\u{3200}'..='\u{32FF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_path() {
+        for c in ('\0'..='\u{3100}').chain('\u{4E00}'..='\u{9FFF}').chain('\u{AC00}'..='\u{D7A3}').chain('\u{FF00}'..='\u{FF20}') {
+            assert_eq!(CharClass::of(c), CharClass::of_unicode(c), "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn test_classes() {
+        let cases = [
+            ('中', CharClass::Han),
+            ('〇', CharClass::Han),
+            ('あ', CharClass::Kana),
+            ('カ', CharClass::Kana),
+            ('ｶ', CharClass::Kana),
+            ('ー', CharClass::Kana),
+            ('ﾞ', CharClass::Kana),
+            ('｢', CharClass::CjkPunctuation),
+            ('･', CharClass::CjkPunctuation),
+            ('・', CharClass::CjkPunctuation),
+            ('〜', CharClass::CjkPunctuation),
+            ('한', CharClass::Hangul),
+            ('ㄅ', CharClass::Bopomofo),
+            ('，', CharClass::CjkPunctuation),
+            ('。', CharClass::CjkPunctuation),
+            ('「', CharClass::CjkPunctuation),
+            ('〽', CharClass::CjkPunctuation),
+            ('￥', CharClass::CjkPunctuation),
+            ('a', CharClass::Letter),
+            ('Ａ', CharClass::Letter),
+            ('α', CharClass::Letter),
+            ('Ж', CharClass::Letter),
+            ('1', CharClass::Digit),
+            ('１', CharClass::Digit),
+            ('$', CharClass::Symbol),
+            ('€', CharClass::Symbol),
+            ('#', CharClass::Symbol),
+            (',', CharClass::Symbol),
+            ('—', CharClass::Ambiguous),
+            ('…', CharClass::Ambiguous),
+            ('“', CharClass::Ambiguous),
+            ('°', CharClass::Ambiguous),
+            ('😀', CharClass::Emoji),
+            ('\u{200D}', CharClass::Emoji),
+            ('\u{FE0F}', CharClass::Emoji),
+            (' ', CharClass::Space),
+            ('\n', CharClass::Space),
+            ('\u{3000}', CharClass::CjkPunctuation),
+        ];
+        for (c, class) in cases {
+            assert_eq!(CharClass::of(c), class, "{:?}", c);
+        }
+    }
+}
@@ -0,0 +1,913 @@
+use clap::{Parser, ValueEnum};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use crate::{
+    Config, Edit, Element, Format, Lang, ParseError, Scope, Spacer, SpacingOptions, SpacingRule, analyze_with_options, options_to_toml,
+    process_with_options, spacing_stream_with_options,
+};
+
+// 添加 diff 相关的依赖
+use console::Style;
+
+#[derive(Parser)]
+#[command(name = "paranoid-space")]
+#[command(about = "auto add space between full-width and half-width characters")]
+#[command(version)]
+#[command(arg_required_else_help = true)]
+struct Cli {
+    /// 需要处理的文件或目录，目录会被递归遍历（遵循 .gitignore/.ignore），如不指定则从标准输入读取
+    paths: Vec<PathBuf>,
+
+    /// 是否直接修改源文件（如不指定则输出到标准输出）
+    #[arg(short = 'i')]
+    in_place: bool,
+
+    /// 是否显示差异对比
+    #[arg(short = 'd', long = "diff")]
+    diff: bool,
+
+    /// 只检查不修改，列出所有需要修改的位置；存在需要修改的内容时以状态码 1 退出
+    #[arg(long, conflicts_with_all = ["in_place", "diff"])]
+    check: bool,
+
+    /// 只处理匹配该 glob 的文件，可多次指定
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// 跳过匹配该 glob 的文件，可多次指定
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// 使用指定的配置文件，不再从各文件所在目录向上查找 paranoid-space.toml
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// 输出指定路径实际生效的配置后退出
+    #[arg(long, value_name = "PATH")]
+    print_config: Option<PathBuf>,
+
+    /// 使用指定语言的规则集：zh（中文，默认）、ja（日文）或 ko（韩文），优先于配置文件中的 lang
+    #[arg(long, value_name = "LANG")]
+    lang: Option<Lang>,
+
+    /// 按指定格式处理：text、html、markdown、css、js、rust、json、json5、php 或注册的格式，不再根据扩展名和内容判断
+    #[arg(long, value_name = "FORMAT")]
+    format: Option<Format>,
+
+    /// 从标准输入读取时，按该路径判断格式、查找配置文件，并在输出中代替 <stdin>
+    #[arg(long, value_name = "PATH")]
+    stdin_filepath: Option<PathBuf>,
+
+    /// 插入的间隔：space（半角空格，默认）、thin（窄空格）、nbsp（不换行空格）或任意内容，HTML 中窄空格和不换行空格写作字符引用
+    #[arg(long, value_name = "SPACER")]
+    spacer: Option<Spacer>,
+
+    /// 只处理给定的内容，以逗号分隔：comments、doc-comments、strings、text，优先于配置文件中的 scope
+    #[arg(long, value_name = "SCOPES", value_delimiter = ',')]
+    scope: Option<Vec<Scope>>,
+
+    /// 两侧文字不视为相连的元素，以逗号分隔：inline、block、line-break（br）、code，默认为 block,line-break
+    #[arg(long, value_name = "ELEMENTS", value_delimiter = ',')]
+    separators: Option<Vec<Element>>,
+
+    /// 把全角字母、数字换成半角，如 `数字１２３和` 改为 `数字 123 和`
+    #[arg(long)]
+    half_width: bool,
+
+    /// 反向处理：去掉中日韩文字与西文之间本会插入空格处的单个半角空格
+    #[arg(long)]
+    unspace: bool,
+
+    /// 文件无法按其格式解析时的处理方式
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = OnParseError::Fail)]
+    on_parse_error: OnParseError,
+}
+
+/// 文件无法按其格式解析时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OnParseError {
+    /// 报告错误并停止处理
+    Fail,
+    /// 报告错误，保持该文件不变
+    Skip,
+    /// 报告错误，把整个文件当作纯文本处理
+    Plain,
+}
+
+/// 检查模式下存在需要修改的内容时的退出状态码
+const EXIT_CHANGES: u8 = 1;
+
+/// 出错（包括文件解析失败）时的退出状态码
+const EXIT_ERROR: u8 = 2;
+
+/// 某个文件实际生效的格式和规则配置
+struct Settings {
+    /// `None` 表示要按内容推测，见 [`Settings::resolve`]
+    format: Option<Format>,
+    options: SpacingOptions,
+}
+
+impl Settings {
+    fn format(&self) -> Format {
+        self.format.unwrap_or(Format::Text)
+    }
+
+    /// 没有指定格式、也无法从路径判断时按内容推测，仍无法判断时作为纯文本
+    fn resolve(self, content: &str) -> Settings {
+        Settings {
+            format: Some(self.format.or_else(|| Format::sniff(content)).unwrap_or(Format::Text)),
+            ..self
+        }
+    }
+}
+
+/// 命令行中覆盖配置文件的规则设置
+#[derive(Debug, Default)]
+struct Overrides {
+    format: Option<Format>,
+    lang: Option<Lang>,
+    spacer: Option<Spacer>,
+    scope: Option<Vec<Scope>>,
+    separators: Option<Vec<Element>>,
+    half_width: bool,
+    unspace: bool,
+}
+
+impl Overrides {
+    fn new(cli: &Cli) -> Self {
+        Self {
+            format: cli.format,
+            lang: cli.lang,
+            spacer: cli.spacer.clone(),
+            scope: cli.scope.clone(),
+            separators: cli.separators.clone(),
+            half_width: cli.half_width,
+            unspace: cli.unspace,
+        }
+    }
+
+    fn apply(&self, mut options: SpacingOptions) -> SpacingOptions {
+        if let Some(lang) = self.lang {
+            options = options.with_lang(lang);
+        }
+        if let Some(spacer) = &self.spacer {
+            options = options.with_spacer(spacer.clone());
+        }
+        if let Some(scope) = &self.scope {
+            options = options.with_scopes(scope.iter().copied());
+        }
+        if let Some(separators) = &self.separators {
+            options = options.with_separators(separators.iter().copied());
+        }
+        if self.half_width {
+            options = options.enable(SpacingRule::HalfWidthAlphanumerics);
+        }
+        if self.unspace {
+            options = options.with_unspacing(true);
+        }
+        options
+    }
+}
+
+/// 合并配置文件、命令行与扩展名得到文件的设置；格式依次取 `--format`、配置文件中的 `[formats]` 和扩展名
+fn settings_for(config: Option<&Config>, file_path: Option<&Path>, overrides: &Overrides) -> Settings {
+    let format = match (config, file_path) {
+        (Some(config), Some(path)) => config.format_for(path),
+        _ => None,
+    };
+    let format = overrides.format.or(format).or_else(|| file_path.and_then(Format::from_path));
+    let options = match (config, file_path) {
+        (Some(config), Some(path)) => config.options_for(path),
+        (Some(config), None) => config.options(),
+        (None, _) => SpacingOptions::default(),
+    };
+    Settings {
+        format,
+        options: overrides.apply(options),
+    }
+}
+
+/// `bytes` 开头的有效 UTF-8 部分；分块读入时最后一个字符可能不完整
+fn utf8_prefix(bytes: &[u8]) -> &str {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+    }
+}
+
+/// 查找各文件对应的配置文件，并按目录缓存
+struct ConfigResolver {
+    /// `--config` 指定的配置文件
+    explicit: Option<Rc<Config>>,
+    discovered: HashMap<PathBuf, Option<Rc<Config>>>,
+}
+
+impl ConfigResolver {
+    fn new(explicit: Option<&Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            explicit: explicit.map(Config::load).transpose()?.map(Rc::new),
+            discovered: HashMap::new(),
+        })
+    }
+
+    fn config_for(&mut self, path: &Path) -> anyhow::Result<Option<Rc<Config>>> {
+        if let Some(config) = &self.explicit {
+            return Ok(Some(config.clone()));
+        }
+        let dir = path.parent().unwrap_or(path).to_path_buf();
+        if let Some(config) = self.discovered.get(&dir) {
+            return Ok(config.clone());
+        }
+        let config = Config::discover(path)?.map(Rc::new);
+        self.discovered.insert(dir, config.clone());
+        Ok(config)
+    }
+}
+
+/// 按文件设置选择合适的处理函数
+fn process_content(content: &str, settings: &Settings) -> anyhow::Result<String> {
+    process_with_options(content, settings.format(), &settings.options)
+}
+
+/// 检查模式：按与 `process_content` 相同的设置处理，返回需要插入的空格
+fn check_content(content: &str, settings: &Settings) -> anyhow::Result<Vec<Edit>> {
+    analyze_with_options(content, settings.format(), &settings.options)
+}
+
+/// 处理失败的描述，解析错误带有出错的行号和列号，形如 `path:line:col: ...`
+fn describe_error(name: &str, format: Format, error: &anyhow::Error) -> String {
+    match error.downcast_ref::<ParseError>() {
+        Some(e) => format!(
+            "{}:{}:{}: failed to parse as {}: {}",
+            name, e.line, e.column, format, e.message
+        ),
+        None => format!("{}: failed to process as {}: {:#}", name, format, error),
+    }
+}
+
+/// `--print-config`：输出路径实际生效的配置，输出内容本身也是合法的配置文件
+fn print_config(config: Option<&Config>, path: &Path, overrides: &Overrides) -> io::Result<()> {
+    let settings = settings_for(config, Some(path), overrides);
+    let mut stdout = io::stdout();
+    match config.and_then(Config::path) {
+        Some(file) => writeln!(stdout, "# config: {}", file.display())?,
+        None => writeln!(stdout, "# config: none, using defaults")?,
+    }
+    match settings.format {
+        Some(format) => writeln!(stdout, "# format: {}", format)?,
+        None => writeln!(stdout, "# format: by content, text if unknown")?,
+    }
+    writeln!(stdout, "# excluded: {}", config.is_some_and(|c| c.is_excluded(path)))?;
+    writeln!(stdout)?;
+    stdout.write_all(options_to_toml(&settings.options).as_bytes())
+}
+
+fn report_error(error: anyhow::Error) -> ExitCode {
+    eprintln!("Error: {:#}", error);
+    ExitCode::from(EXIT_ERROR)
+}
+
+/// 多个文件的结果写到标准输出会首尾相接、无法区分，只能就地修改、检查或显示差异
+fn check_output(cli: &Cli, files: usize) -> anyhow::Result<()> {
+    if files > 1 && !(cli.in_place || cli.check || cli.diff) {
+        anyhow::bail!("{} files to process; use -i, --check or --diff with more than one file", files);
+    }
+    Ok(())
+}
+
+/// 检查模式下的一条输出，形如 `path:line:col: 原文 -> 修改后 [规则]`
+fn format_finding(name: &str, content: &str, edit: &Edit) -> String {
+    const CONTEXT: usize = 10;
+    let line_start = content[..edit.offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = content[edit.offset..]
+        .find(['\r', '\n'])
+        .map_or(content.len(), |i| edit.offset + i);
+
+    let before: Vec<char> = content[line_start..edit.offset].chars().collect();
+    let before: String = before[before.len().saturating_sub(CONTEXT)..].iter().collect();
+    let original: String = content[edit.offset..line_end].chars().take(CONTEXT).collect();
+    let after = original.strip_prefix(edit.removed.as_str()).unwrap_or(&original);
+
+    format!(
+        "{}:{}:{}: {}{} -> {}{}{} [{}]",
+        name, edit.line, edit.column, before, original, before, edit.inserted, after, edit.rule
+    )
+}
+
+/// 根据 `--include`、`--exclude` 过滤文件
+struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        fn build(globs: &[String]) -> Result<GlobSet, globset::Error> {
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(Glob::new(glob)?);
+            }
+            builder.build()
+        }
+
+        let include = if include.is_empty() { None } else { Some(build(include)?) };
+        Ok(Self {
+            include,
+            exclude: build(exclude)?,
+        })
+    }
+
+    fn is_match(&self, path: &Path) -> bool {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        self.include.as_ref().is_none_or(|set| set.is_match(path)) && !self.exclude.is_match(path)
+    }
+}
+
+/// 展开命令行中的路径：目录递归遍历并遵循 .gitignore/.ignore，文件直接保留
+fn collect_files(paths: &[PathBuf], filter: &PathFilter) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let walker = WalkBuilder::new(path)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+        for entry in walker {
+            match entry {
+                Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => {
+                    files.push(entry.into_path())
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Warning: {}", e),
+            }
+        }
+    }
+    files.retain(|file| filter.is_match(file));
+    files
+}
+
+/// 读取文本文件，二进制或非 UTF-8 文件返回 `None`
+fn read_text(path: &Path) -> io::Result<Option<String>> {
+    const SNIFF_LEN: usize = 8192;
+    let bytes = fs::read(path)?;
+    if bytes[..bytes.len().min(SNIFF_LEN)].contains(&0) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(bytes).ok())
+}
+
+/// 多文件处理的统计
+#[derive(Debug, Default)]
+struct Summary {
+    scanned: usize,
+    changed: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+/// 显示原始内容和处理后内容的彩色差异，行对比方式
+fn show_diff(original: &str, processed: &str) -> io::Result<()> {
+    // 定义样式
+    let added = Style::new().green();
+    let removed = Style::new().red();
+    let unchanged = Style::new().dim(); // 用于显示无变化的行
+    let line_num_style = Style::new().cyan().dim(); // 行号样式
+
+    // 存储原始行和处理后行，用于配对显示
+    let original_lines: Vec<&str> = original.lines().collect();
+    let processed_lines: Vec<&str> = processed.lines().collect();
+
+    // 计算行号显示宽度（根据总行数确定）
+    let total_lines = original_lines.len().max(processed_lines.len());
+    let line_width = total_lines.to_string().len();
+
+    let mut line_num = 0;
+    let mut in_unchanged_block = false; // 跟踪是否在连续无变化的区块中
+
+    // 打印空首行
+    writeln!(io::stdout())?;
+
+    // 逐行配对显示差异
+    while line_num < original_lines.len() || line_num < processed_lines.len() {
+        // 只有在两者都存在并且内容相同时才显示为"无变化"
+        if line_num < original_lines.len()
+            && line_num < processed_lines.len()
+            && original_lines[line_num] == processed_lines[line_num]
+        {
+            // 记录无变化区块的开始
+            if !in_unchanged_block {
+                in_unchanged_block = true;
+                writeln!(io::stdout(), "{}", unchanged.apply_to("..."))?;
+            }
+
+            line_num += 1;
+            continue;
+        }
+
+        // 当遇到有变化的行时，重置无变化区块标志
+        in_unchanged_block = false;
+
+        // 显示原始行（如果存在）
+        if line_num < original_lines.len() {
+            write!(
+                io::stdout(),
+                "{} ",
+                line_num_style.apply_to(format!("{:0width$}", line_num + 1, width = line_width))
+            )?;
+            writeln!(
+                io::stdout(),
+                "{} {}",
+                removed.apply_to("-"),
+                removed.apply_to(original_lines[line_num])
+            )?;
+        }
+
+        // 显示处理后行（如果存在）
+        if line_num < processed_lines.len() {
+            write!(
+                io::stdout(),
+                "{} ",
+                line_num_style.apply_to(format!("{:0width$}", line_num + 1, width = line_width))
+            )?;
+            writeln!(
+                io::stdout(),
+                "{} {}",
+                added.apply_to("+"),
+                added.apply_to(processed_lines[line_num])
+            )?;
+        }
+
+        line_num += 1;
+    }
+
+    // 打印空尾行
+    writeln!(io::stdout())?;
+
+    Ok(())
+}
+
+/// 单个文件的处理结果
+#[derive(Debug, Default)]
+struct Outcome {
+    /// 内容需要修改
+    changed: bool,
+    /// 文件无法解析
+    failed: bool,
+}
+
+/// 按设置运行 `f`；失败时报告错误，`--on-parse-error=plain` 时改按纯文本重新运行，否则返回 `None`
+fn run<T>(
+    cli: &Cli,
+    name: &str,
+    settings: &Settings,
+    outcome: &mut Outcome,
+    f: impl Fn(&Settings) -> anyhow::Result<T>,
+) -> Option<T> {
+    let error = match f(settings) {
+        Ok(value) => return Some(value),
+        Err(error) => error,
+    };
+    outcome.failed = true;
+    let description = describe_error(name, settings.format(), &error);
+    match cli.on_parse_error {
+        OnParseError::Fail => {
+            eprintln!("Error: {}", description);
+            None
+        }
+        OnParseError::Skip => {
+            eprintln!("Warning: {}, skipped", description);
+            None
+        }
+        OnParseError::Plain => {
+            eprintln!("Warning: {}, processed as plain text", description);
+            let plain = Settings {
+                format: Some(Format::Text),
+                options: settings.options.clone(),
+            };
+            f(&plain).ok()
+        }
+    }
+}
+
+/// 处理单个文件，`path` 为 `None` 时表示标准输入
+fn process_file(
+    cli: &Cli,
+    path: Option<&Path>,
+    content: &str,
+    settings: &Settings,
+    show_name: bool,
+) -> io::Result<Outcome> {
+    let name = match path.or(cli.stdin_filepath.as_deref()) {
+        Some(path) => path.display().to_string(),
+        None => "<stdin>".to_string(),
+    };
+    let mut outcome = Outcome::default();
+
+    if cli.check {
+        if let Some(edits) = run(cli, &name, settings, &mut outcome, |settings| {
+            check_content(content, settings)
+        }) {
+            for edit in &edits {
+                writeln!(io::stdout(), "{}", format_finding(&name, content, edit))?;
+            }
+            outcome.changed = !edits.is_empty();
+        }
+        return Ok(outcome);
+    }
+
+    let in_place = path.filter(|_| cli.in_place);
+    let Some(result) = run(cli, &name, settings, &mut outcome, |settings| {
+        process_content(content, settings)
+    }) else {
+        // 跳过的内容原样输出，保持管道中的内容完整
+        if cli.on_parse_error == OnParseError::Skip && !cli.diff && in_place.is_none() {
+            io::stdout().write_all(content.as_bytes())?;
+        }
+        return Ok(outcome);
+    };
+    outcome.changed = result != content;
+
+    if cli.diff {
+        // 显示差异
+        if show_name {
+            writeln!(io::stdout(), "{}", Style::new().bold().apply_to(&name))?;
+        }
+        show_diff(content, &result)?;
+    } else if let Some(path) = in_place {
+        // 直接修改源文件
+        if outcome.changed {
+            fs::write(path, result)?;
+        }
+    } else {
+        // 输出到标准输出
+        io::stdout().write_all(result.as_bytes())?;
+    }
+    Ok(outcome)
+}
+
+/// 命令行入口：解析命令行参数并处理
+///
+/// 通过 [`register`](crate::register) 注册了其他格式的程序可以调用它，得到识别这些格式的命令行工具。
+pub fn main() -> io::Result<ExitCode> {
+    let cli = Cli::parse();
+    let overrides = Overrides::new(&cli);
+
+    let mut configs = match ConfigResolver::new(cli.config.as_deref()) {
+        Ok(configs) => configs,
+        Err(e) => return Ok(report_error(e)),
+    };
+
+    if let Some(path) = &cli.print_config {
+        return match configs.config_for(path) {
+            Ok(config) => print_config(config.as_deref(), path, &overrides).map(|_| ExitCode::SUCCESS),
+            Err(e) => Ok(report_error(e)),
+        };
+    }
+
+    if cli.paths.is_empty() {
+        // 从标准输入读取，配置文件从 --stdin-filepath 所在目录或当前目录开始查找
+        let path = cli.stdin_filepath.as_deref();
+        let config = match configs.config_for(&std::env::current_dir()?.join(path.unwrap_or(Path::new("-")))) {
+            Ok(config) => config,
+            Err(e) => return Ok(report_error(e)),
+        };
+        if let (Some(config), Some(path)) = (&config, path)
+            && config.is_excluded(path)
+        {
+            if !cli.check && !cli.diff {
+                io::copy(&mut io::stdin().lock(), &mut io::stdout().lock())?;
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        // 无法从路径判断格式时，按已读入的第一块内容推测，不必等待全部输入
+        let mut stdin = io::stdin().lock();
+        let settings = settings_for(config.as_deref(), path, &overrides).resolve(utf8_prefix(stdin.fill_buf()?));
+
+        // 纯文本直接输出时流式处理，不必读入全部内容
+        if settings.format() == Format::Text && !cli.check && !cli.diff {
+            let stdout = io::BufWriter::new(io::stdout().lock());
+            return match spacing_stream_with_options(stdin, stdout, &settings.options) {
+                Ok(()) => Ok(ExitCode::SUCCESS),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    let name = path.map_or_else(|| "<stdin>".to_string(), |path| path.display().to_string());
+                    Ok(report_error(anyhow::Error::new(e).context(name)))
+                }
+                Err(e) => Err(e),
+            };
+        }
+
+        let mut buffer = String::new();
+        stdin.read_to_string(&mut buffer)?;
+
+        let outcome = process_file(&cli, None, &buffer, &settings, false)?;
+        if outcome.failed {
+            return Ok(ExitCode::from(EXIT_ERROR));
+        }
+        if cli.check && outcome.changed {
+            return Ok(ExitCode::from(EXIT_CHANGES));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let filter = PathFilter::new(&cli.include, &cli.exclude).map_err(io::Error::other)?;
+    let files = collect_files(&cli.paths, &filter);
+    if let Err(e) = check_output(&cli, files.len()) {
+        return Ok(report_error(e));
+    }
+    let show_name = files.len() > 1;
+
+    let mut summary = Summary::default();
+    for file in &files {
+        let config = match configs.config_for(file) {
+            Ok(config) => config,
+            Err(e) => return Ok(report_error(e)),
+        };
+        if config.as_ref().is_some_and(|config| config.is_excluded(file)) {
+            continue;
+        }
+        let Some(content) = read_text(file)? else {
+            summary.skipped += 1;
+            continue;
+        };
+        let settings = settings_for(config.as_deref(), Some(file), &overrides).resolve(&content);
+        summary.scanned += 1;
+        let outcome = process_file(&cli, Some(file), &content, &settings, show_name)?;
+        if outcome.changed {
+            summary.changed += 1;
+        }
+        if outcome.failed {
+            summary.failed += 1;
+            if cli.on_parse_error == OnParseError::Fail {
+                break;
+            }
+        }
+    }
+
+    let changed = if cli.in_place { "changed" } else { "would change" };
+    eprintln!(
+        "{} file(s) scanned, {} {}, {} skipped (binary), {} failed",
+        summary.scanned, summary.changed, changed, summary.skipped, summary.failed
+    );
+
+    if summary.failed > 0 {
+        return Ok(ExitCode::from(EXIT_ERROR));
+    }
+    if cli.check && summary.changed > 0 {
+        return Ok(ExitCode::from(EXIT_CHANGES));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_file(content: &str, name: &str) -> Vec<Edit> {
+        check_content(content, &settings_for(None, Some(Path::new(name)), &Overrides::default())).unwrap()
+    }
+
+    #[test]
+    fn test_format_finding() {
+        let content = "第一行\n这是中文English混合内容";
+        let edits = check_file(content, "a.txt");
+        assert_eq!(
+            format_finding("a.txt", content, &edits[0]),
+            "a.txt:2:5: 这是中文English混合内 -> 这是中文 English混合内 [full-half]"
+        );
+        assert_eq!(
+            format_finding("a.txt", content, &edits[1]),
+            "a.txt:2:12: 是中文English混合内容 -> 是中文English 混合内容 [half-full]"
+        );
+
+        let options = SpacingOptions::new().enable(SpacingRule::FullWidthPunctuation);
+        let edits = analyze_with_options("你好,世界", Format::Text, &options).unwrap();
+        assert_eq!(
+            format_finding("a.txt", "你好,世界", &edits[0]),
+            "a.txt:1:3: 你好,世界 -> 你好，世界 [full-width-punctuation]"
+        );
+    }
+
+    #[test]
+    fn test_describe_error() {
+        let settings = settings_for(None, Some(Path::new("a.json")), &Overrides::default());
+        let error = process_content("{\n  \"a\": }", &settings).unwrap_err();
+        assert!(
+            describe_error("a.json", settings.format(), &error)
+                .starts_with("a.json:2:8: failed to parse as json: expected ")
+        );
+
+        let error = anyhow::anyhow!("boom");
+        assert_eq!(
+            describe_error("a.css", Format::Css, &error),
+            "a.css: failed to process as css: boom"
+        );
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let filter = PathFilter::new(&["*.md".to_string()], &["drafts/**".to_string()]).unwrap();
+        assert!(filter.is_match(Path::new("docs/a.md")));
+        assert!(filter.is_match(Path::new("./a.md")));
+        assert!(!filter.is_match(Path::new("docs/a.rs")));
+        assert!(!filter.is_match(Path::new("drafts/a.md")));
+
+        let filter = PathFilter::new(&[], &["*.min.js".to_string()]).unwrap();
+        assert!(filter.is_match(Path::new("a.js")));
+        assert!(!filter.is_match(Path::new("dist/a.min.js")));
+    }
+
+    #[test]
+    fn test_check_output() {
+        let cli = Cli::try_parse_from(["paranoid-space", "docs/"]).unwrap();
+        assert!(check_output(&cli, 1).is_ok());
+        assert!(check_output(&cli, 2).is_err());
+        for flag in ["-i", "--check", "--diff"] {
+            let cli = Cli::try_parse_from(["paranoid-space", flag, "docs/"]).unwrap();
+            assert!(check_output(&cli, 2).is_ok(), "{}", flag);
+        }
+    }
+
+    #[test]
+    fn test_collect_files() {
+        let root = std::env::temp_dir().join(format!("paranoid-space-walk-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("docs/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join(".ignore"), "*.bak\n").unwrap();
+        fs::write(root.join("docs/a.md"), "中文").unwrap();
+        fs::write(root.join("docs/nested/b.html"), "中文").unwrap();
+        fs::write(root.join("docs/c.bak"), "中文").unwrap();
+        fs::write(root.join("target/d.md"), "中文").unwrap();
+        fs::write(root.join("e.bin"), [0u8, 1, 2]).unwrap();
+
+        let filter = PathFilter::new(&[], &[]).unwrap();
+        let files = collect_files(std::slice::from_ref(&root), &filter);
+        let names: Vec<_> = files
+            .iter()
+            .map(|f| f.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("docs/a.md"),
+                PathBuf::from("docs/nested/b.html"),
+                PathBuf::from("e.bin")
+            ]
+        );
+        assert_eq!(read_text(&root.join("docs/a.md")).unwrap().as_deref(), Some("中文"));
+        assert_eq!(read_text(&root.join("e.bin")).unwrap(), None);
+
+        let filter = PathFilter::new(&["**/*.html".to_string()], &[]).unwrap();
+        assert_eq!(collect_files(std::slice::from_ref(&root), &filter).len(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_check_uses_file_type() {
+        let content = "<p title=\"标题Title\">段落Text</p>";
+        assert_eq!(check_file(content, "a.html").len(), 2);
+        assert_eq!(check_file(content, "a.txt").len(), 3);
+        assert!(check_file("{}", "a.json").is_empty());
+    }
+
+    #[test]
+    fn test_settings_from_config() {
+        let config: Config = r#"
+[formats]
+"*.tpl" = "html"
+
+[[overrides]]
+files = ["**/*.js"]
+scope = ["comments"]
+"#
+        .parse()
+        .unwrap();
+        let content = "<p title=\"标题Title\">段落Text</p>";
+        let settings = settings_for(Some(&config), Some(Path::new("a.tpl")), &Overrides::default());
+        assert_eq!(settings.format, Some(Format::Html));
+        assert_eq!(check_content(content, &settings).unwrap().len(), 2);
+
+        let settings = settings_for(Some(&config), Some(Path::new("src/a.js")), &Overrides::default());
+        assert_eq!(settings.format, Some(Format::Js));
+        assert_eq!(
+            process_content("let s = \"字符串String\"; // 注释Comment", &settings).unwrap(),
+            "let s = \"字符串String\"; // 注释 Comment"
+        );
+    }
+
+    #[test]
+    fn test_format_detection() {
+        let settings = settings_for(None, Some(Path::new("src/lib.rs")), &Overrides::default());
+        assert_eq!(settings.format, Some(Format::Rust));
+        // 无法从扩展名判断时按内容推测
+        let settings = settings_for(None, Some(Path::new("bin/tool")), &Overrides::default());
+        assert_eq!(settings.format, None);
+        assert_eq!(settings.resolve("#!/usr/bin/env node\n// 注释").format, Some(Format::Js));
+        let settings = settings_for(None, Some(Path::new("README")), &Overrides::default());
+        assert_eq!(settings.resolve("普通文本").format, Some(Format::Text));
+
+        // 标准输入按第一块内容推测，块末尾不完整的字符不影响判断
+        let head = "<!DOCTYPE html>\n<p>段落".as_bytes();
+        assert_eq!(utf8_prefix(&head[..head.len() - 1]), "<!DOCTYPE html>\n<p>段");
+        let settings = settings_for(None, None, &Overrides::default());
+        assert_eq!(settings.resolve(utf8_prefix(&head[..head.len() - 1])).format, Some(Format::Html));
+        let settings = settings_for(None, Some(Path::new("a.txt")), &Overrides::default());
+        assert_eq!(settings.resolve(utf8_prefix(head)).format, Some(Format::Text));
+
+        let overrides = Overrides {
+            format: Some(Format::Markdown),
+            ..Overrides::default()
+        };
+        let settings = settings_for(None, Some(Path::new("a.txt")), &overrides);
+        // 行内代码保持不变
+        assert_eq!(process_content("`code代码`段落", &settings).unwrap(), "`code代码`段落");
+    }
+
+    #[test]
+    fn test_lang_override() {
+        let config: Config = "lang = \"ja\"".parse().unwrap();
+        let settings = settings_for(Some(&config), Some(Path::new("a.txt")), &Overrides::default());
+        assert_eq!(process_content("第3章", &settings).unwrap(), "第3章");
+
+        let overrides = Overrides {
+            lang: Some(Lang::Zh),
+            ..Overrides::default()
+        };
+        let settings = settings_for(Some(&config), Some(Path::new("a.txt")), &overrides);
+        assert_eq!(process_content("第3章", &settings).unwrap(), "第 3 章");
+
+        let overrides = Overrides {
+            half_width: true,
+            ..Overrides::default()
+        };
+        let settings = settings_for(Some(&config), Some(Path::new("a.txt")), &overrides);
+        assert_eq!(process_content("第３章", &settings).unwrap(), "第3章");
+
+        let overrides = Overrides {
+            unspace: true,
+            ..Overrides::default()
+        };
+        let settings = settings_for(None, Some(Path::new("a.js")), &overrides);
+        assert_eq!(process_content("// 注释 Comment", &settings).unwrap(), "// 注释Comment");
+        let edits = check_content("// 注释 Comment", &settings).unwrap();
+        assert_eq!(
+            format_finding("a.js", "// 注释 Comment", &edits[0]),
+            "a.js:1:6: // 注释 Comment -> // 注释Comment [full-half]"
+        );
+    }
+
+    #[test]
+    fn test_spacer_override() {
+        let overrides = Overrides {
+            spacer: Some(Spacer::Thin),
+            ..Overrides::default()
+        };
+        let settings = settings_for(None, Some(Path::new("a.html")), &overrides);
+        assert_eq!(
+            process_content("<p title=\"标题Title\">中文English</p>", &settings).unwrap(),
+            "<p title=\"标题&thinsp;Title\">中文&thinsp;English</p>"
+        );
+        let settings = settings_for(None, Some(Path::new("a.md")), &overrides);
+        assert_eq!(process_content("中文English", &settings).unwrap(), "中文\u{2009}English");
+    }
+
+    #[test]
+    fn test_scope_override() {
+        let cli = Cli::try_parse_from(["paranoid-space", "--scope", "comments,doc-comments"]).unwrap();
+        let overrides = Overrides::new(&cli);
+        let settings = settings_for(None, Some(Path::new("a.js")), &overrides);
+        assert_eq!(
+            process_content("let s = '字符串String'; // 注释Comment", &settings).unwrap(),
+            "let s = '字符串String'; // 注释 Comment"
+        );
+
+        // The command line wins over the config file
+        let config: Config = "scope = [\"strings\"]".parse().unwrap();
+        let settings = settings_for(Some(&config), Some(Path::new("a.rs")), &overrides);
+        assert_eq!(
+            process_content("/// 文档Doc\nlet s = \"字符串String\";", &settings).unwrap(),
+            "/// 文档 Doc\nlet s = \"字符串String\";"
+        );
+        assert!(Cli::try_parse_from(["paranoid-space", "--scope", "code"]).is_err());
+    }
+
+    #[test]
+    fn test_separators_override() {
+        let cli = Cli::try_parse_from(["paranoid-space", "--separators", "block,br,code"]).unwrap();
+        let settings = settings_for(None, Some(Path::new("a.md")), &Overrides::new(&cli));
+        assert_eq!(process_content("中文`code`和**Bold**", &settings).unwrap(), "中文`code`和 **Bold**");
+        assert!(Cli::try_parse_from(["paranoid-space", "--separators", "table"]).is_err());
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context as _, Result, anyhow};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{Element, Format, Lang, Scope, Spacer, SpacingOptions, SpacingRule};
+
+/// 配置文件名，从被处理文件所在目录开始逐级向上查找
+pub const CONFIG_FILE_NAME: &str = "paranoid-space.toml";
+
+/// 项目配置，对应 `paranoid-space.toml`
+///
+/// 配置中的 glob 以配置文件所在目录为基准匹配路径。只使用最近的一个配置文件，不会逐级合并。
+///
+/// ```toml
+/// exclude = ["vendor/**", "*.min.js"]
+/// lang = "zh"
+/// spacer = "thin"
+/// exceptions = ["C语言"]
+/// separators = ["block", "line-break", "code"]
+///
+/// [rules]
+/// currency = false
+///
+/// [chars]
+/// after-half-symbol = "@#~"
+///
+/// [formats]
+/// "*.vue" = "html"
+///
+/// [[overrides]]
+/// files = ["**/*.js"]
+/// scope = ["comments", "doc-comments"]
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use paranoid_space::{Config, Format, Scope, SpacingRule};
+///
+/// let config: Config = r#"
+/// exclude = ["dist/**"]
+///
+/// [rules]
+/// currency = false
+///
+/// [formats]
+/// "*.vue" = "html"
+///
+/// [[overrides]]
+/// files = ["**/*.js"]
+/// scope = ["comments"]
+/// "#
+/// .parse()
+/// .unwrap();
+///
+/// assert!(config.is_excluded(Path::new("dist/app.js")));
+/// assert_eq!(config.format_for(Path::new("src/App.vue")), Some(Format::Html));
+///
+/// let options = config.options_for(Path::new("src/app.js"));
+/// assert!(!options.is_enabled(SpacingRule::Currency));
+/// assert!(options.in_scope(Scope::Comments));
+/// assert!(!options.in_scope(Scope::Strings));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    path: Option<PathBuf>,
+    root: Option<PathBuf>,
+    settings: Settings,
+    exclude: GlobSet,
+    formats: Vec<(GlobSet, Format)>,
+    overrides: Vec<(GlobSet, Settings)>,
+}
+
+impl Config {
+    /// 读取指定的配置文件
+    pub fn load(path: &Path) -> Result<Config> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut config: Config = content
+            .parse()
+            .with_context(|| format!("invalid config {}", path.display()))?;
+        let path = std::path::absolute(path)?;
+        config.root = path.parent().map(Path::to_path_buf);
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    /// 从 `start`（文件或目录）开始逐级向上查找并读取配置文件
+    pub fn discover(start: &Path) -> Result<Option<Config>> {
+        let start = std::path::absolute(start)?;
+        let dir = if start.is_dir() { Some(start.as_path()) } else { start.parent() };
+        for dir in dir.into_iter().flat_map(Path::ancestors) {
+            let candidate = dir.join(CONFIG_FILE_NAME);
+            if candidate.is_file() {
+                return Config::load(&candidate).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// 配置文件的路径，由字符串解析得到的配置返回 `None`
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// 配置文件所在目录，配置中的 glob 相对于该目录
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.exclude.is_match(self.relative(path))
+    }
+
+    /// `[formats]` 中为该路径指定的格式
+    pub fn format_for(&self, path: &Path) -> Option<Format> {
+        let path = self.relative(path);
+        self.formats
+            .iter()
+            .rev()
+            .find(|(globs, _)| globs.is_match(&path))
+            .map(|&(_, format)| format)
+    }
+
+    /// 顶层设置对应的规则配置，不含 `[[overrides]]`
+    pub fn options(&self) -> SpacingOptions {
+        self.settings.apply(SpacingOptions::default())
+    }
+
+    /// 该路径生效的规则配置：默认配置依次叠加顶层设置和所有匹配的 `[[overrides]]`
+    pub fn options_for(&self, path: &Path) -> SpacingOptions {
+        let path = self.relative(path);
+        self.overrides
+            .iter()
+            .filter(|(globs, _)| globs.is_match(&path))
+            .fold(self.options(), |options, (_, settings)| settings.apply(options))
+    }
+
+    fn relative(&self, path: &Path) -> PathBuf {
+        if let Some(root) = &self.root
+            && let Ok(absolute) = std::path::absolute(path)
+            && let Ok(relative) = absolute.strip_prefix(root)
+        {
+            return relative.to_path_buf();
+        }
+        path.strip_prefix(".").unwrap_or(path).to_path_buf()
+    }
+}
+
+impl FromStr for Config {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw: RawConfig = toml::from_str(s)?;
+        deny_unknown(&raw.unknown)?;
+
+        let mut formats = Vec::with_capacity(raw.formats.len());
+        for (glob, name) in &raw.formats {
+            formats.push((glob_set(std::slice::from_ref(glob))?, name.parse()?));
+        }
+        let mut overrides = Vec::with_capacity(raw.overrides.len());
+        for raw_override in &raw.overrides {
+            deny_unknown(&raw_override.unknown)?;
+            if raw_override.files.is_empty() {
+                return Err(anyhow!("`files` of [[overrides]] must not be empty"));
+            }
+            overrides.push((
+                glob_set(&raw_override.files)?,
+                Settings::try_from(&raw_override.settings)?,
+            ));
+        }
+
+        Ok(Config {
+            path: None,
+            root: None,
+            settings: Settings::try_from(&raw.settings)?,
+            exclude: glob_set(&raw.exclude)?,
+            formats,
+            overrides,
+        })
+    }
+}
+
+/// 将规则配置输出为配置文件格式，可直接作为 `paranoid-space.toml` 使用
+pub fn options_to_toml(options: &SpacingOptions) -> String {
+    let settings = RawSettings {
+        lang: Some(options.lang().id().to_string()),
+        spacer: Some(options.spacer().to_string()),
+        rules: SpacingRule::ALL
+            .into_iter()
+            .map(|rule| (rule.id().to_string(), options.is_enabled(rule)))
+            .collect(),
+        chars: SpacingRule::ALL
+            .into_iter()
+            .filter_map(|rule| {
+                let chars = options.chars(rule)?;
+                Some((rule.id().to_string(), chars.iter().collect()))
+            })
+            .collect(),
+        exceptions: options.exceptions().iter().cloned().collect(),
+        scope: Some(options.scopes().iter().map(|scope| scope.id().to_string()).collect()),
+        separators: Some(options.separators().iter().map(|element| element.id().to_string()).collect()),
+    };
+    toml::to_string(&settings).expect("settings are always representable in TOML")
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Settings that may appear both at the top level and in `[[overrides]]`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RawSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lang: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spacer: Option<String>,
+    #[serde(default)]
+    exceptions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    separators: Option<Vec<String>>,
+    #[serde(default)]
+    rules: BTreeMap<String, bool>,
+    #[serde(default)]
+    chars: BTreeMap<String, String>,
+}
+
+// `deny_unknown_fields` does not work together with `flatten`, so leftover
+// keys are collected into `unknown` and rejected when validating.
+#[derive(Debug, Deserialize)]
+struct RawConfig {
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    formats: BTreeMap<String, String>,
+    #[serde(default)]
+    overrides: Vec<RawOverride>,
+    #[serde(flatten)]
+    settings: RawSettings,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawOverride {
+    files: Vec<String>,
+    #[serde(flatten)]
+    settings: RawSettings,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+fn deny_unknown(unknown: &BTreeMap<String, toml::Value>) -> Result<()> {
+    match unknown.keys().next() {
+        Some(key) => Err(anyhow!("unknown config key `{}`", key)),
+        None => Ok(()),
+    }
+}
+
+/// Validated form of [`RawSettings`].
+#[derive(Debug, Clone, Default)]
+struct Settings {
+    lang: Option<Lang>,
+    spacer: Option<Spacer>,
+    exceptions: Vec<String>,
+    scope: Option<Vec<Scope>>,
+    separators: Option<Vec<Element>>,
+    rules: Vec<(SpacingRule, bool)>,
+    chars: Vec<(SpacingRule, String)>,
+}
+
+impl Settings {
+    fn apply(&self, mut options: SpacingOptions) -> SpacingOptions {
+        // The language comes first, as it changes the defaults the rest modifies
+        if let Some(lang) = self.lang {
+            options = options.with_lang(lang);
+        }
+        if let Some(spacer) = &self.spacer {
+            options = options.with_spacer(spacer.clone());
+        }
+        for &(rule, enabled) in &self.rules {
+            options = options.set_rule(rule, enabled);
+        }
+        for (rule, chars) in &self.chars {
+            options = options.with_chars(*rule, chars);
+        }
+        if let Some(scope) = &self.scope {
+            options = options.with_scopes(scope.iter().copied());
+        }
+        if let Some(separators) = &self.separators {
+            options = options.with_separators(separators.iter().copied());
+        }
+        options.add_exceptions(self.exceptions.iter().cloned())
+    }
+}
+
+impl TryFrom<&RawSettings> for Settings {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: &RawSettings) -> Result<Self> {
+        let mut chars = Vec::with_capacity(raw.chars.len());
+        for (id, set) in &raw.chars {
+            let rule: SpacingRule = id.parse()?;
+            if rule.default_chars(Lang::default()).is_empty() {
+                return Err(anyhow!("spacing rule {} has no character set", rule));
+            }
+            chars.push((rule, set.clone()));
+        }
+        Ok(Settings {
+            lang: raw.lang.as_deref().map(str::parse).transpose()?,
+            spacer: raw.spacer.as_deref().map(str::parse).transpose()?,
+            exceptions: raw.exceptions.clone(),
+            scope: raw
+                .scope
+                .as_ref()
+                .map(|scope| scope.iter().map(|id| id.parse()).collect())
+                .transpose()?,
+            separators: raw
+                .separators
+                .as_ref()
+                .map(|separators| separators.iter().map(|id| id.parse()).collect())
+                .transpose()?,
+            rules: raw
+                .rules
+                .iter()
+                .map(|(id, &enabled)| Ok((id.parse()?, enabled)))
+                .collect::<Result<_>>()?,
+            chars,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides() {
+        let config: Config = r#"
+exceptions = ["C语言"]
+
+[rules]
+currency = false
+
+[[overrides]]
+files = ["**/*.js"]
+scope = ["comments"]
+exceptions = ["Wi-Fi网络"]
+
+[[overrides]]
+files = ["legacy/**"]
+[overrides.rules]
+currency = true
+[overrides.chars]
+after-half-symbol = "@"
+"#
+        .parse()
+        .unwrap();
+
+        let options = config.options_for(Path::new("docs/a.md"));
+        assert!(!options.is_enabled(SpacingRule::Currency));
+        assert!(options.in_scope(Scope::Strings));
+        assert_eq!(options.exceptions().len(), 1);
+
+        let options = config.options_for(Path::new("./legacy/a.js"));
+        assert!(options.is_enabled(SpacingRule::Currency));
+        assert!(!options.in_scope(Scope::Strings));
+        assert_eq!(options.exceptions().len(), 2);
+        assert_eq!(
+            options.chars(SpacingRule::AfterHalfSymbol).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        for content in [
+            "[rules]\nno-such-rule = true",
+            "[chars]\nfull-half = \"a\"",
+            "scope = [\"code\"]",
+            "separators = [\"table\"]",
+            "unknown-key = 1",
+            "[formats]\n\"*.vue\" = \"vue\"",
+            "[[overrides]]\nfiles = []",
+            "exclude = [\"[\"]",
+            "lang = \"fr\"",
+            "spacer = \"\"",
+        ] {
+            assert!(content.parse::<Config>().is_err(), "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_options_to_toml_round_trip() {
+        let options = SpacingOptions::new()
+            .disable(SpacingRule::LineStart)
+            .with_chars(SpacingRule::Currency, "$")
+            .add_exceptions(["iPhone"])
+            .with_scopes([Scope::Comments, Scope::Text])
+            .with_separators([Element::Block, Element::Code]);
+        let config: Config = options_to_toml(&options).parse().unwrap();
+        assert_eq!(config.options_for(Path::new("a.txt")), options);
+
+        let options = SpacingOptions::new().with_lang(Lang::Ja).enable(SpacingRule::Digits);
+        let config: Config = options_to_toml(&options).parse().unwrap();
+        assert_eq!(config.options_for(Path::new("a.txt")), options);
+
+        for spacer in [Spacer::Thin, Spacer::Custom("<span class=\"gap\"></span>".to_string())] {
+            let options = SpacingOptions::new().with_spacer(spacer);
+            let config: Config = options_to_toml(&options).parse().unwrap();
+            assert_eq!(config.options_for(Path::new("a.txt")), options);
+        }
+    }
+
+    #[test]
+    fn test_discover() {
+        let root = std::env::temp_dir().join(format!("paranoid-space-config-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("docs/nested")).unwrap();
+        fs::write(
+            root.join(CONFIG_FILE_NAME),
+            "exclude = [\"docs/drafts/**\"]\n[rules]\ncurrency = false\n",
+        )
+        .unwrap();
+
+        let file = root.join("docs/nested/a.md");
+        let config = Config::discover(&file).unwrap().unwrap();
+        assert_eq!(config.path(), Some(root.join(CONFIG_FILE_NAME).as_path()));
+        assert!(!config.options_for(&file).is_enabled(SpacingRule::Currency));
+        assert!(config.is_excluded(&root.join("docs/drafts/b.md")));
+        assert!(!config.is_excluded(&file));
+
+        fs::write(root.join("docs/nested").join(CONFIG_FILE_NAME), "").unwrap();
+        let config = Config::discover(&file).unwrap().unwrap();
+        assert!(config.options_for(&file).is_enabled(SpacingRule::Currency));
+
+        fs::write(root.join("docs").join(CONFIG_FILE_NAME), "[rules]\nbad = 1").unwrap();
+        assert!(Config::discover(&root.join("docs/a.md")).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
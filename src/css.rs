@@ -1,6 +1,8 @@
 use pest::Parser;
 use pest_derive::Parser;
-use crate::spacing;
+use crate::{ParseError, SpacingOptions};
+use crate::edit::Context;
+use crate::segment::{CommentStyle, Segment, SegmentKind, Segments};
 use anyhow::Result;
 
 #[derive(Parser)]
@@ -8,43 +10,33 @@ use anyhow::Result;
 pub struct CssParser;
 
 pub fn process(input: &str) -> Result<String> {
-    let pairs = CssParser::parse(Rule::css, input)?;
-    let mut result: Vec<String> = Vec::new();
+    process_with_options(input, &SpacingOptions::default())
+}
+
+pub fn process_with_options(input: &str, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(input, &segment(input)?))
+}
+
+pub(crate) fn segment(input: &str) -> Result<Vec<Segment>> {
+    let pairs = CssParser::parse(Rule::css, input).map_err(ParseError::from)?;
+    let mut segments = Segments::new();
 
     if let Some(css_pair) = pairs.peek() {
         if css_pair.as_rule() == Rule::css {
             let inner_pairs = css_pair.clone().into_inner();
 
             for pair in inner_pairs {
+                let (start, end) = (pair.as_span().start(), pair.as_span().end());
                 match pair.as_rule() {
-                    Rule::COMMENT => {
-                        let s = pair.as_str();
-                        if s.len() >= 4 {
-                            let content = &s[2..s.len() - 2];
-                            let spaced_content = spacing(content);
-                            result.push(format!("/*{}*/", spaced_content));
-                        } else {
-                            result.push(s.to_owned());
-                        }
-                    }
-                    Rule::STRING => {
-                        let s = pair.as_str();
-                        if s.len() >= 2 {
-                            let quote = &s[0..1];
-                            let content = &s[1..s.len() - 1];
-                            let spaced_content = spacing(content);
-                            result.push(format!("{}{}{}", quote, spaced_content, quote));
-                        } else {
-                            result.push(s.to_owned());
-                        }
-                    }
-                    Rule::WHITESPACE | Rule::ELSE_CONTENT => {
-                        result.push(pair.as_str().to_owned());
+                    Rule::COMMENT if pair.as_str().len() >= 4 => {
+                        segments.push(SegmentKind::Comment { style: CommentStyle::Block }, start + 2..end - 2);
                     }
-                    Rule::EOI => {
-                        // Do nothing, EOI is handled implicitly by loop end
+                    Rule::STRING if pair.as_str().len() >= 2 => {
+                        let quote = pair.as_str().chars().next();
+                        segments.push(SegmentKind::StringLiteral { quote }, start + 1..end - 1);
                     }
-                    _ => { /* Rule::css should not appear here */ }
+                    // Whitespace, other content and EOI stay as they are
+                    _ => {}
                 }
             }
         } else {
@@ -52,7 +44,7 @@ pub fn process(input: &str) -> Result<String> {
         }
     }
 
-    Ok(result.join(""))
+    Ok(segments.finish(input.len()))
 }
 
 #[cfg(test)]
@@ -0,0 +1,109 @@
+use std::ops::Range;
+
+/// Every directive starts with this, so inputs without it skip the collecting pass.
+pub(crate) const PREFIX: &str = "paranoid-space";
+
+/// An inline comment that excludes part of the input from spacing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Directive {
+    /// `paranoid-space-ignore`: the line the comment is on
+    Ignore,
+    /// `paranoid-space-ignore-next-line`: the line after the comment
+    IgnoreNextLine,
+    /// `paranoid-space off`: everything up to the next `paranoid-space on`
+    Off,
+    On,
+}
+
+impl Directive {
+    /// Recognizes a directive in a comment, with or without its delimiters.
+    /// Anything after the directive, such as a reason, is allowed.
+    pub(crate) fn parse(comment: &str) -> Option<Directive> {
+        let body = comment
+            .trim()
+            .trim_start_matches(['/', '*', '#', '!', '<', '-'])
+            .trim_end_matches(['/', '*', '-', '>']);
+        let mut words = body.split_whitespace().map(|word| word.trim_end_matches(':'));
+        match (words.next()?, words.next()) {
+            ("paranoid-space-ignore", _) => Some(Directive::Ignore),
+            ("paranoid-space-ignore-next-line", _) => Some(Directive::IgnoreNextLine),
+            ("paranoid-space", Some("off")) => Some(Directive::Off),
+            ("paranoid-space", Some("on")) => Some(Directive::On),
+            _ => None,
+        }
+    }
+}
+
+/// Resolves the directives found in `input`, each with the range of its
+/// comment, into the sorted byte ranges they exclude.
+pub(crate) fn ignored_ranges(input: &str, directives: &[(Directive, Range<usize>)]) -> Vec<Range<usize>> {
+    let line_start = |offset: usize| input[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = |offset: usize| input[offset..].find('\n').map_or(input.len(), |i| offset + i);
+
+    let mut ranges = Vec::new();
+    let mut off: Option<usize> = None;
+    for (directive, range) in directives {
+        match directive {
+            Directive::Ignore => ranges.push(line_start(range.start)..line_end(range.end)),
+            Directive::IgnoreNextLine => {
+                let end = line_end(range.end);
+                if end < input.len() {
+                    ranges.push(end + 1..line_end(end + 1));
+                }
+            }
+            Directive::Off => {
+                off.get_or_insert(range.end);
+            }
+            Directive::On => {
+                if let Some(start) = off.take() {
+                    ranges.push(start..range.start);
+                }
+            }
+        }
+    }
+    if let Some(start) = off {
+        ranges.push(start..input.len());
+    }
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Directive::parse("// paranoid-space-ignore"), Some(Directive::Ignore));
+        assert_eq!(
+            Directive::parse("/* paranoid-space-ignore-next-line: protocol literal */"),
+            Some(Directive::IgnoreNextLine)
+        );
+        assert_eq!(Directive::parse("<!-- paranoid-space off -->"), Some(Directive::Off));
+        assert_eq!(Directive::parse("# paranoid-space on"), Some(Directive::On));
+        assert_eq!(Directive::parse(" paranoid-space on "), Some(Directive::On));
+        assert_eq!(Directive::parse("// paranoid-space"), None);
+        assert_eq!(Directive::parse("// paranoid-space-ignored"), None);
+        assert_eq!(Directive::parse("// see paranoid-space off"), None);
+    }
+
+    #[test]
+    fn test_ignored_ranges() {
+        let input = "a // paranoid-space-ignore\nb\n// paranoid-space-ignore-next-line\nc\nd";
+        let directives = [
+            (Directive::Ignore, 2..26),
+            (Directive::IgnoreNextLine, 29..63),
+        ];
+        assert_eq!(ignored_ranges(input, &directives), vec![0..26, 64..65]);
+        // Nothing follows the last line
+        assert!(ignored_ranges("// x", &[(Directive::IgnoreNextLine, 0..4)]).is_empty());
+
+        let directives = [
+            (Directive::Off, 0..2),
+            (Directive::On, 5..7),
+            (Directive::On, 8..9),
+            (Directive::Off, 10..12),
+        ];
+        assert_eq!(ignored_ranges("0123456789abcdef", &directives), vec![2..5, 12..16]);
+    }
+}
@@ -0,0 +1,359 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::directive::{self, Directive};
+use crate::segment::{self, Segment, SegmentKind};
+use crate::{Format, Scope, SpacingOptions, SpacingRule, context_edits, transform};
+
+/// 一次修改的记录：插入空格，或把 `removed` 替换为 `inserted`（如半角标点换成全角）
+///
+/// 位置均以原始输入为准：`offset` 是字节偏移，`line`、`column` 从 1 开始，`column` 以字符计。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    /// 修改处之前的字符
+    pub before: Option<char>,
+    /// 修改处之后的字符，不含被替换的内容
+    pub after: Option<char>,
+    /// 被替换的原文，插入空格时为空
+    pub removed: String,
+    pub inserted: String,
+    pub rule: SpacingRule,
+}
+
+/// 返回按指定格式处理 `input` 时的所有修改，按位置排序
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::{Format, SpacingRule, analyze};
+///
+/// let edits = analyze("let s = \"你好world\";", Format::Js).unwrap();
+/// assert_eq!(edits.len(), 1);
+/// assert_eq!((edits[0].offset, edits[0].line, edits[0].column), (15, 1, 12));
+/// assert_eq!((edits[0].before, edits[0].after), (Some('好'), Some('w')));
+/// assert_eq!(edits[0].rule, SpacingRule::FullHalf);
+/// ```
+pub fn analyze(input: &str, format: Format) -> Result<Vec<Edit>> {
+    analyze_with_options(input, format, &SpacingOptions::default())
+}
+
+pub fn analyze_with_options(
+    input: &str,
+    format: Format,
+    options: &SpacingOptions,
+) -> Result<Vec<Edit>> {
+    let mut ctx = Context::tracking(options);
+    ctx.run(input, &format.segment(input)?);
+    Ok(ctx.into_edits(input))
+}
+
+/// An edit recorded while processing, before line/column resolution.
+#[derive(Debug, Clone)]
+pub(crate) struct RawEdit {
+    offset: usize,
+    /// Length in bytes of the replaced text, 0 for insertions
+    removed: usize,
+    inserted: String,
+    rule: SpacingRule,
+}
+
+/// State threaded through the format processors: the spacing options, plus an
+/// optional log of edits mapped back to offsets in the top-level input.
+///
+/// All offsets kept here are in the top-level input.
+pub(crate) struct Context<'a> {
+    pub(crate) options: &'a SpacingOptions,
+    base: usize,
+    /// Set while processing content embedded in another scope, e.g. Markdown
+    /// inside a doc comment, so that everything nested counts as that scope.
+    scope: Option<Scope>,
+    edits: Option<Vec<RawEdit>>,
+    /// Ranges excluded by ignore directives, sorted by start
+    ignored: Vec<Range<usize>>,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(options: &'a SpacingOptions) -> Self {
+        Self {
+            options,
+            base: 0,
+            scope: None,
+            edits: None,
+            ignored: Vec::new(),
+        }
+    }
+
+    pub(crate) fn tracking(options: &'a SpacingOptions) -> Self {
+        Self {
+            options,
+            base: 0,
+            scope: None,
+            edits: Some(Vec::new()),
+            ignored: Vec::new(),
+        }
+    }
+
+    /// Spaces the top-level `input`, split into `segments`. Directives are
+    /// collected from the comments first, so that a directive also covers the
+    /// text that comes before it, e.g. with `paranoid-space-ignore`.
+    pub(crate) fn run(&mut self, input: &str, segments: &[Segment]) -> String {
+        if input.contains(directive::PREFIX) {
+            let directives: Vec<(Directive, Range<usize>)> = segments
+                .iter()
+                .filter(|segment| matches!(segment.kind, SegmentKind::Comment { .. }))
+                .filter_map(|segment| {
+                    let text = &input[segment.range.clone()];
+                    // Line comments may include their line break
+                    let range = segment.range.start..segment.range.start + text.trim_end().len();
+                    Directive::parse(text).map(|directive| (directive, range))
+                })
+                .collect();
+            self.ignored = directive::ignored_ranges(input, &directives);
+        }
+        segment::apply(input, segments, self)
+    }
+
+    /// Applies spacing to `text`, which starts at `offset` in the current input,
+    /// unless `scope` is not selected in the options.
+    pub(crate) fn spacing<'t>(&mut self, scope: Scope, text: &'t str, offset: usize) -> Cow<'t, str> {
+        let options = self.options;
+        self.spacing_with(scope, text, offset, options.spacer().as_str())
+    }
+
+    /// Like [`Context::spacing`] for HTML text, which writes the spacer as a
+    /// character reference. `markup` tells whether tags may be written here,
+    /// which is not the case in attribute values or `<title>`.
+    pub(crate) fn html_spacing<'t>(&mut self, scope: Scope, text: &'t str, offset: usize, markup: bool) -> Cow<'t, str> {
+        let options = self.options;
+        self.spacing_with(scope, text, offset, options.spacer().html(markup))
+    }
+
+    fn spacing_with<'t>(&mut self, scope: Scope, text: &'t str, offset: usize, spacer: &str) -> Cow<'t, str> {
+        if !self.in_scope(scope) {
+            return Cow::Borrowed(text);
+        }
+        let scope = self.scope.unwrap_or(scope);
+        let base = self.base + offset;
+        let ignored: Vec<Range<usize>> = self
+            .ignored
+            .iter()
+            .filter(|range| range.start <= base + text.len() && base <= range.end)
+            .map(|range| range.start.saturating_sub(base)..range.end - base.min(range.end))
+            .collect();
+        match self.edits.as_mut() {
+            Some(edits) => transform(text, self.options, scope, spacer, &ignored, |range, inserted, rule| {
+                edits.push(RawEdit {
+                    offset: base + range.start,
+                    removed: range.len(),
+                    inserted: inserted.to_string(),
+                    rule,
+                })
+            }),
+            None => transform(text, self.options, scope, spacer, &ignored, |_, _, _| {}),
+        }
+    }
+
+    /// The rule by which a spacer goes between `prev` and `text`, when they are
+    /// only split by inline markup, unless `scope` is not selected.
+    pub(crate) fn joins(&self, scope: Scope, prev: char, text: &str, spacer: &str) -> Option<SpacingRule> {
+        if !self.in_scope(scope) {
+            return None;
+        }
+        // Rules look no further than the word next to the boundary
+        let word = &text[..text.find(char::is_whitespace).unwrap_or(text.len())];
+        context_edits(Some(prev), word, None, self.options, self.scope.unwrap_or(scope), spacer)
+            .into_iter()
+            .find(|(range, inserted, _)| range.is_empty() && range.start == 0 && !inserted.is_empty())
+            .map(|(_, _, rule)| rule)
+    }
+
+    /// Records a spacer inserted at `offset` in the current input.
+    pub(crate) fn insert(&mut self, offset: usize, spacer: &str, rule: SpacingRule) {
+        if let Some(edits) = self.edits.as_mut() {
+            edits.push(RawEdit {
+                offset: self.base + offset,
+                removed: 0,
+                inserted: spacer.to_string(),
+                rule,
+            });
+        }
+    }
+
+    /// Whether `range` of the current input overlaps a range excluded by a directive.
+    pub(crate) fn is_ignored(&self, range: Range<usize>) -> bool {
+        let (start, end) = (self.base + range.start, self.base + range.end);
+        self.ignored.iter().any(|ignored| ignored.start < end && start < ignored.end)
+    }
+
+    pub(crate) fn in_scope(&self, scope: Scope) -> bool {
+        self.options.in_scope(self.scope.unwrap_or(scope))
+    }
+
+    /// Runs `f` with everything it spaces counted as `scope`.
+    pub(crate) fn scoped<R>(&mut self, scope: Scope, f: impl FnOnce(&mut Self) -> R) -> R {
+        let outer = self.scope;
+        self.scope = Some(outer.unwrap_or(scope));
+        let result = f(self);
+        self.scope = outer;
+        result
+    }
+
+    /// Runs `f` on a sub-input that starts at `offset` in the current input.
+    pub(crate) fn nested<R>(&mut self, offset: usize, f: impl FnOnce(&mut Self) -> R) -> R {
+        let base = self.base;
+        self.base += offset;
+        let result = f(self);
+        self.base = base;
+        result
+    }
+
+    /// Runs `f` on text that is not a contiguous slice of the current input,
+    /// translating the offsets it records with `map`.
+    pub(crate) fn remapped<R>(
+        &mut self,
+        map: impl Fn(usize) -> usize,
+        f: impl FnOnce(&mut Context) -> R,
+    ) -> R {
+        let mut child = Context {
+            options: self.options,
+            base: 0,
+            scope: self.scope,
+            edits: self.edits.as_ref().map(|_| Vec::new()),
+            // Offsets in the child are not in the input, so callers check
+            // `is_ignored` for the whole text instead
+            ignored: Vec::new(),
+        };
+        let result = f(&mut child);
+        if let (Some(edits), Some(child_edits)) = (self.edits.as_mut(), child.edits) {
+            edits.extend(child_edits.into_iter().map(|edit| RawEdit {
+                offset: self.base + map(edit.offset),
+                ..edit
+            }));
+        }
+        result
+    }
+
+    fn into_edits(self, input: &str) -> Vec<Edit> {
+        let mut raw = self.edits.unwrap_or_default();
+        // An insertion goes before a replacement at the same offset
+        raw.sort_by_key(|edit| (edit.offset, edit.removed));
+
+        let mut edits = Vec::with_capacity(raw.len());
+        let (mut line, mut column, mut pos) = (1, 1, 0);
+        for edit in raw {
+            for c in input[pos..edit.offset].chars() {
+                if c == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+            }
+            pos = edit.offset;
+            let end = edit.offset + edit.removed;
+            edits.push(Edit {
+                offset: edit.offset,
+                line,
+                column,
+                before: input[..edit.offset].chars().next_back(),
+                after: input[end..].chars().next(),
+                removed: input[edit.offset..end].to_string(),
+                inserted: edit.inserted,
+                rule: edit.rule,
+            });
+        }
+        edits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(input: &str, format: Format) -> Vec<(usize, usize, usize)> {
+        analyze(input, format)
+            .unwrap()
+            .iter()
+            .map(|edit| {
+                assert_eq!(edit.inserted, " ");
+                (edit.offset, edit.line, edit.column)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_text() {
+        let edits = analyze("中文English\n123中文", Format::Text).unwrap();
+        assert_eq!(edits.len(), 2);
+        assert_eq!((edits[0].offset, edits[0].line, edits[0].column), (6, 1, 3));
+        assert_eq!((edits[0].before, edits[0].after), (Some('文'), Some('E')));
+        assert_eq!(edits[0].rule, SpacingRule::FullHalf);
+        assert_eq!((edits[1].offset, edits[1].line, edits[1].column), (17, 2, 4));
+        assert_eq!((edits[1].before, edits[1].after), (Some('3'), Some('中')));
+        assert_eq!(edits[1].rule, SpacingRule::HalfFull);
+        assert!(analyze("没有需要处理的内容", Format::Text).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_edits_reproduce_output() {
+        let cases = [
+            (Format::Js, "let a = `模板${x}Literal`; // 注释comment\n/*块block*/"),
+            (Format::Css, "a { content: \"内容Content\"; } /* 注释Comment */"),
+            (Format::Json, r#"{"a": "甲b", "c": ["乙c"]}"#),
+            (Format::Json5, "{ a: '甲b', // 注释c\n}"),
+            (Format::Html, "<p title=\"标题Title\">段落Text</p><!-- 注释Comment --><script>// 脚本JS\n</script>"),
+            (Format::Php, "<p>段落Text</p><?php echo \"$x双引号String\"; // 注释Comment ?>"),
+            (Format::Rust, "/// 文档Doc\n/// 第二行Line\nfn main() { let s = \"字符串String\"; } // 注释Comment"),
+            (Format::Rust, "/**\n * 块文档Doc\n */"),
+            (Format::Html, "<p>中文<a href=\"#\"><b>Link</b></a>文字</p>"),
+            (Format::Markdown, "中文**bold**和`code`文字"),
+            (Format::Rust, "/// 中文[`Vec`]类型\nfn f() {}"),
+        ];
+        let options = SpacingOptions::default();
+        for (format, input) in cases {
+            let expected = Context::new(&options).run(input, &format.segment(input).unwrap());
+            let edits = analyze(input, format).unwrap();
+            assert!(!edits.is_empty(), "{:?}", input);
+            let mut rebuilt = input.to_string();
+            for edit in edits.iter().rev() {
+                rebuilt.replace_range(edit.offset..edit.offset + edit.removed.len(), &edit.inserted);
+            }
+            assert_eq!(rebuilt, expected, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_replacements() {
+        let options = SpacingOptions::new().enable(SpacingRule::FullWidthPunctuation);
+        let input = "你好,世界(Hello)和(函数)";
+        let edits = analyze_with_options(input, Format::Text, &options).unwrap();
+        let summary: Vec<_> = edits
+            .iter()
+            .map(|edit| (edit.offset, edit.removed.as_str(), edit.inserted.as_str(), edit.rule))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (6, ",", "，", SpacingRule::FullWidthPunctuation),
+                (13, "", " ", SpacingRule::FullHalf),
+                (20, "", " ", SpacingRule::HalfFull),
+                (23, "(", "（", SpacingRule::FullWidthPunctuation),
+                (30, ")", "）", SpacingRule::FullWidthPunctuation),
+            ]
+        );
+        assert_eq!((edits[0].before, edits[0].after), (Some('好'), Some('世')));
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            positions("# 标题Title\n\n段落[链接Link](http://example.com)", Format::Markdown),
+            vec![(8, 1, 5), (28, 3, 6)]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use pest::RuleType;
+use pest::error::LineColLocation;
+
+/// 输入不符合对应格式的语法，无法解析
+///
+/// `process_*` 和 `analyze` 返回的 `anyhow::Error` 可以通过 `downcast_ref::<ParseError>()` 取得出错位置。
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::{ParseError, process_json};
+///
+/// let error = process_json("{\n  \"a\": }").unwrap_err();
+/// let error = error.downcast_ref::<ParseError>().unwrap();
+/// assert_eq!((error.line, error.column), (2, 8));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 出错的行号，从 1 开始
+    pub line: usize,
+    /// 出错的列号，从 1 开始，以字符计
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// Locates the error of a part of `input` that starts at `offset`, such as
+    /// a `<script>` body, in the whole input; other errors are returned as is.
+    pub(crate) fn within(error: anyhow::Error, input: &str, offset: usize) -> anyhow::Error {
+        let Some(e) = error.downcast_ref::<ParseError>() else {
+            return error;
+        };
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = if e.line == 1 { e.column + before[line_start..].chars().count() } else { e.column };
+        ParseError {
+            line: e.line + before.matches('\n').count(),
+            column,
+            message: e.message.clone(),
+        }
+        .into()
+    }
+}
+
+impl<R: RuleType> From<pest::error::Error<R>> for ParseError {
+    fn from(error: pest::error::Error<R>) -> Self {
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+        };
+        ParseError {
+            line,
+            column,
+            message: error.variant.message().into_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within() {
+        // A JSON body starting on line 2, column 9 of the document
+        let input = "<p>段落</p>\n<script>{\n  \"a\": }</script>";
+        let offset = input.find('{').unwrap();
+        let error = crate::process_json("{\n  \"a\": }").unwrap_err();
+        let error = ParseError::within(error, input, offset);
+        let error = error.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((error.line, error.column), (3, 8));
+
+        let error = crate::process_json("}").unwrap_err();
+        let error = ParseError::within(error, input, offset);
+        assert_eq!(error.downcast_ref::<ParseError>().unwrap().column, 9);
+
+        // Other errors are kept
+        let error = ParseError::within(anyhow::anyhow!("其他错误"), input, offset);
+        assert!(error.downcast_ref::<ParseError>().is_none());
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock};
+
+use anyhow::{Result, anyhow};
+use regex::Regex;
+
+use crate::edit::Context;
+use crate::segment::{Segment, SegmentKind, Segments};
+use crate::processor::{self, FormatProcessor};
+use crate::{DEFAULT_OPTIONS, SpacingOptions, css, html, js, json, json5, markdown, php, rust};
+
+/// Modelines are looked for in this many lines at the start and the end.
+const MODELINE_LINES: usize = 5;
+
+/// Content heuristics only look at the start of the input, except for JSON.
+const SNIFF_LEN: usize = 8192;
+
+/// `vim: set ft=markdown:`, `vi: filetype=html`
+static VIM_MODELINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?:vi|vim|ex):.*\b(?:ft|filetype)=([\w+-]+)").unwrap());
+
+/// `-*- mode: markdown -*-`, `-*- mode: js; coding: utf-8 -*-`, `-*- markdown -*-`
+static EMACS_MODELINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"-\*-\s*(?:[^;]*?;\s*)*?mode:\s*([\w+-]+)|-\*-\s*([\w+-]+)\s*-\*-").unwrap()
+});
+
+/// ATX headings, code fences and inline links at the start of a line
+static MARKDOWN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^(?:#{1,6} \S|```|~~~|.*\[[^\]\n]+\]\([^)\s]+\))").unwrap());
+
+/// Closing tags of common HTML elements
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)</(?:html|head|body|div|p|span|a|ul|ol|li|table|section|article|main|nav|h[1-6])>").unwrap()
+});
+
+/// 支持的输入格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// 纯文本，整体调用 `spacing`
+    Text,
+    Html,
+    Markdown,
+    Css,
+    Js,
+    Rust,
+    Json,
+    Json5,
+    Php,
+    /// 通过 [`register`](crate::register) 注册的格式
+    Custom(&'static str),
+}
+
+impl Format {
+    pub const ALL: [Format; 9] = [
+        Format::Text,
+        Format::Html,
+        Format::Markdown,
+        Format::Css,
+        Format::Js,
+        Format::Rust,
+        Format::Json,
+        Format::Json5,
+        Format::Php,
+    ];
+
+    /// 格式名称，用于配置文件和命令行
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Text => "text",
+            Format::Html => "html",
+            Format::Markdown => "markdown",
+            Format::Css => "css",
+            Format::Js => "js",
+            Format::Rust => "rust",
+            Format::Json => "json",
+            Format::Json5 => "json5",
+            Format::Php => "php",
+            Format::Custom(name) => name,
+        }
+    }
+
+    /// Extensions of the built-in formats, in lowercase
+    pub(crate) fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Format::Text => &["txt", "text"],
+            Format::Html => &["html", "htm", "xhtml", "shtml"],
+            Format::Markdown => &["md", "markdown", "mdown", "mkd", "mkdn"],
+            Format::Css => &["css"],
+            Format::Js => &["js", "mjs", "cjs"],
+            Format::Rust => &["rs"],
+            Format::Json => &["json"],
+            Format::Json5 => &["json5", "jsonc"],
+            Format::Php => &["php", "phtml"],
+            Format::Custom(_) => &[],
+        }
+    }
+
+    /// 根据扩展名判断格式，不区分大小写，后注册的格式优先，无法判断时返回 `None`
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use paranoid_space::Format;
+    ///
+    /// assert_eq!(Format::from_path(Path::new("src/main.rs")), Some(Format::Rust));
+    /// assert_eq!(Format::from_path(Path::new("README.MD")), Some(Format::Markdown));
+    /// assert_eq!(Format::from_path(Path::new("Makefile")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<Format> {
+        processor::by_extension(path.extension()?.to_str()?)
+    }
+
+    /// 根据首行的 shebang 判断格式，如 `#!/usr/bin/env node`、`#!/usr/bin/php`
+    pub fn from_shebang(content: &str) -> Option<Format> {
+        let line = content.strip_prefix("#!")?.lines().next()?;
+        let mut words = line.split_whitespace().map(|word| word.rsplit('/').next().unwrap_or(word));
+        let mut interpreter = words.next()?;
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+        }
+        // Versioned interpreters, like `php8.2` or `node18`
+        match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "node" | "nodejs" | "deno" | "bun" | "qjs" => Some(Format::Js),
+            "php" => Some(Format::Php),
+            "rust-script" | "cargo" => Some(Format::Rust),
+            _ => None,
+        }
+    }
+
+    /// 根据开头或结尾几行中 Vim、Emacs 的 modeline 判断格式，如 `vim: set ft=markdown:`、`-*- mode: html -*-`
+    pub fn from_modeline(content: &str) -> Option<Format> {
+        let lines: Vec<&str> = content.lines().collect();
+        let tail = lines.len().saturating_sub(MODELINE_LINES).max(MODELINE_LINES.min(lines.len()));
+        lines[..MODELINE_LINES.min(lines.len())]
+            .iter()
+            .chain(&lines[tail..])
+            .find_map(|line| {
+                let captures = VIM_MODELINE.captures(line).or_else(|| EMACS_MODELINE.captures(line))?;
+                let name = captures.iter().skip(1).flatten().next()?.as_str();
+                name.parse().ok()
+            })
+    }
+
+    /// 根据内容推测格式：先看 shebang 和 modeline，再看 `<?php`、HTML 标签、JSON、Markdown 标记等特征，
+    /// 都不符合时返回 `None`，通常按纯文本处理
+    ///
+    /// ```
+    /// use paranoid_space::Format;
+    ///
+    /// assert_eq!(Format::sniff("#!/usr/bin/env node\nconsole.log(1)"), Some(Format::Js));
+    /// assert_eq!(Format::sniff("<!DOCTYPE html>\n<p>段落</p>"), Some(Format::Html));
+    /// assert_eq!(Format::sniff("# 标题\n\n正文"), Some(Format::Markdown));
+    /// assert_eq!(Format::sniff("只是一段文字"), None);
+    /// ```
+    pub fn sniff(content: &str) -> Option<Format> {
+        if let Some(format) = Format::from_shebang(content).or_else(|| Format::from_modeline(content)) {
+            return Some(format);
+        }
+        let text = content.trim_start_matches('\u{feff}').trim();
+        let mut end = text.len().min(SNIFF_LEN);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let head = &text[..end];
+        let lowercase = head[..head.len().min(64)].to_ascii_lowercase();
+        if lowercase.starts_with("<?php") || lowercase.starts_with("<?=") {
+            return Some(Format::Php);
+        }
+        if lowercase.starts_with("<!doctype html") || lowercase.starts_with("<html") {
+            return Some(Format::Html);
+        }
+        if head.starts_with('<') && HTML_TAG.is_match(head) {
+            return Some(Format::Html);
+        }
+        if (text.starts_with('{') && text.ends_with('}')) || (text.starts_with('[') && text.ends_with(']')) {
+            if json::process(text).is_ok() {
+                return Some(Format::Json);
+            }
+            if json5::process(text).is_ok() {
+                return Some(Format::Json5);
+            }
+        }
+        if MARKDOWN.is_match(head) {
+            return Some(Format::Markdown);
+        }
+        None
+    }
+
+    /// Built-in format names, including those used by editors
+    pub(crate) fn from_alias(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" | "txt" | "plain" | "plaintext" => Some(Format::Text),
+            "html" | "htm" | "xhtml" => Some(Format::Html),
+            "markdown" | "md" | "gfm" => Some(Format::Markdown),
+            "css" => Some(Format::Css),
+            "js" | "javascript" | "js2" => Some(Format::Js),
+            "rust" | "rs" => Some(Format::Rust),
+            "json" => Some(Format::Json),
+            "json5" | "jsonc" => Some(Format::Json5),
+            "php" => Some(Format::Php),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn segment(self, input: &str) -> Result<Vec<Segment>> {
+        match self {
+            Format::Text => {
+                let mut segments = Segments::new();
+                segments.push(SegmentKind::Text, 0..input.len());
+                Ok(segments.finish(input.len()))
+            }
+            Format::Html => html::segment(input),
+            Format::Markdown => markdown::segment(input),
+            Format::Css => css::segment(input),
+            Format::Js => js::segment(input),
+            Format::Rust => rust::segment(input),
+            Format::Json => json::segment(input),
+            Format::Json5 => json5::segment(input),
+            Format::Php => php::segment(input),
+            Format::Custom(_) => self.processor()?.segment(input),
+        }
+    }
+
+    fn processor(self) -> Result<Arc<dyn FormatProcessor>> {
+        processor::processor(self).ok_or_else(|| anyhow!("unknown format: {}", self))
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 格式名称，也接受 `md`、`javascript`、`rs` 等常见别名
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Format::from_alias(s)
+            .or_else(|| processor::by_name(s))
+            .ok_or_else(|| anyhow!("unknown format: {}", s))
+    }
+}
+
+/// 按指定格式处理，格式可由 [`Format::from_path`]、[`Format::sniff`] 等判断
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::{Format, process};
+///
+/// assert_eq!(process("# 标题Title", Format::Markdown).unwrap(), "# 标题 Title");
+/// assert_eq!(process("<p>中文English</p>", Format::Html).unwrap(), "<p>中文 English</p>");
+/// ```
+pub fn process(input: &str, format: Format) -> Result<String> {
+    process_with_options(input, format, &DEFAULT_OPTIONS)
+}
+
+/// 按指定格式和规则配置处理
+pub fn process_with_options(input: &str, format: Format, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(input, &format.segment(input)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        let cases = [
+            ("a.htm", Some(Format::Html)),
+            ("a.XHTML", Some(Format::Html)),
+            ("docs/a.mdown", Some(Format::Markdown)),
+            ("lib.mjs", Some(Format::Js)),
+            ("src/main.rs", Some(Format::Rust)),
+            ("a.rust", None),
+            ("tsconfig.jsonc", Some(Format::Json5)),
+            ("notes.txt", Some(Format::Text)),
+            ("LICENSE", None),
+        ];
+        for (path, format) in cases {
+            assert_eq!(Format::from_path(Path::new(path)), format, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_from_shebang() {
+        let cases = [
+            ("#!/usr/bin/env node\n", Some(Format::Js)),
+            ("#!/usr/bin/env -S deno run --allow-net\n", Some(Format::Js)),
+            ("#!/usr/local/bin/php8.2 -q\n<?php", Some(Format::Php)),
+            ("#!/usr/bin/env -S cargo +nightly -Zscript\n", Some(Format::Rust)),
+            ("#!/bin/sh\n", None),
+            ("# 标题\n", None),
+        ];
+        for (content, format) in cases {
+            assert_eq!(Format::from_shebang(content), format, "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_from_modeline() {
+        assert_eq!(Format::from_modeline("正文\n\n<!-- vim: set ft=markdown: -->\n"), Some(Format::Markdown));
+        assert_eq!(Format::from_modeline("// -*- mode: js; coding: utf-8 -*-\n"), Some(Format::Js));
+        assert_eq!(Format::from_modeline("/* -*- css -*- */"), Some(Format::Css));
+        // Only the first and last lines count
+        let content = format!("{}vim: ft=html\n{}", "行\n".repeat(6), "行\n".repeat(6));
+        assert_eq!(Format::from_modeline(&content), None);
+        assert_eq!(Format::from_modeline("vim: ft=cobol"), None);
+    }
+
+    #[test]
+    fn test_sniff() {
+        let cases = [
+            ("\u{feff}<?php echo '你好';", Some(Format::Php)),
+            ("<html><body>正文</body></html>", Some(Format::Html)),
+            ("<div class=\"a\">\n  <p>段落</p>\n</div>", Some(Format::Html)),
+            ("{\"key\": \"值\"}", Some(Format::Json)),
+            ("{key: '值', // 注释\n}", Some(Format::Json5)),
+            ("```rust\nfn main() {}\n```", Some(Format::Markdown)),
+            ("参见[文档](https://example.com)。", Some(Format::Markdown)),
+            ("{不是 JSON}", None),
+            ("<不是标签>", None),
+            ("#话题 不是标题", None),
+        ];
+        for (content, format) in cases {
+            assert_eq!(Format::sniff(content), format, "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_process() {
+        let options = SpacingOptions::new().with_unspacing(true);
+        assert_eq!(process_with_options("// 注释 Comment", Format::Js, &options).unwrap(), "// 注释Comment");
+        assert_eq!(process("<p>中文English</p>", Format::Text).unwrap(), "<p> 中文 English</p>");
+        assert!(process("{", Format::Json).is_err());
+        assert_eq!("javascript".parse::<Format>().unwrap(), Format::Js);
+    }
+}
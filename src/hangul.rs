@@ -0,0 +1,49 @@
+use crate::CharClass;
+
+/// Particles and copula endings that attach to the word before them, such as
+/// `를` in `API를`; several may follow each other, as in `Git에서는`.
+const PARTICLES: &[&str] = &[
+    "이", "가", "을", "를", "은", "는", "의", "에", "에서", "에게", "한테", "께", "께서", "로", "으로",
+    "서", "써", "와", "과", "랑", "이랑", "하고", "도", "만", "까지", "부터", "보다", "처럼", "마다",
+    "조차", "마저", "밖에", "뿐", "나", "이나", "든지", "이든지", "라도", "이라도", "요", "야", "이야",
+    "다", "이다", "입니다", "이에요", "예요", "였다", "이었다", "인", "란", "이란", "라는", "이라는",
+    "라고", "이라고",
+];
+
+/// Syllables that start a suffix or a verb ending attached to a foreign word:
+/// `push했다`, `API화`, `user들`.
+const SUFFIXES: &[char] = &[
+    '하', '해', '했', '한', '할', '함', '합', '된', '되', '돼', '됐', '화', '적', '들', '님', '씨',
+];
+
+/// Whether the Hangul word at the start of `text` is written attached to the
+/// Latin word or number before it.
+pub(crate) fn is_attached(text: &str) -> bool {
+    let end = text
+        .find(|c| CharClass::of(c) != CharClass::Hangul)
+        .unwrap_or(text.len());
+    let word = &text[..end];
+    !word.is_empty() && (word.starts_with(SUFFIXES) || is_particles(word))
+}
+
+fn is_particles(word: &str) -> bool {
+    word.is_empty()
+        || PARTICLES
+            .iter()
+            .any(|particle| word.strip_prefix(particle).is_some_and(is_particles))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_attached() {
+        for text in ["를", "로 작성", "에서는", "으로도", "입니다.", "했다", "화된", "들을"] {
+            assert!(is_attached(text), "{}", text);
+        }
+        for text in ["프로그래밍", "이름", "언어를", "", "abc"] {
+            assert!(!is_attached(text), "{}", text);
+        }
+    }
+}
@@ -1,165 +1,354 @@
 // src/html.rs
+use std::ops::Range;
+use std::sync::LazyLock;
+
 use anyhow::Result;
-use pest::Parser;
-use pest::iterators::Pair;
-// 导入spacing函数和其他处理函数
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{
+    BufferQueue, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+use regex::Regex;
+
 use crate::css;
 use crate::js;
-use crate::spacing;
-
-#[derive(Parser)]
-#[grammar = "grammar/html.pest"] // 相对于src的路径
-pub struct HtmlParser;
+use crate::{Element, ParseError, SpacingOptions};
+use crate::edit::Context;
+use crate::segment::{CommentStyle, Markup, Segment, SegmentKind, Segments};
+
+/// 会显示给用户、需要添加空格的属性；`value` 另见 [`is_spaced`]
+const SPACED_ATTRIBUTES: &[&str] = &[
+    "title",
+    "alt",
+    "placeholder",
+    "label",
+    "summary",
+    "content",
+    "aria-label",
+    "aria-description",
+    "aria-placeholder",
+    "aria-roledescription",
+    "aria-valuetext",
+];
+
+/// 默认按行内显示的元素，标签两侧的文字视为相连；`br`、代码和其余元素另行归类
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "cite", "data", "del", "dfn", "em", "font", "i", "ins", "label", "mark", "q",
+    "ruby", "s", "small", "span", "strong", "sub", "sup", "time", "u",
+];
+
+/// HTML 的元素，用于区分 Markdown 中的标签和 `Vec<T>` 这样的泛型
+const ELEMENTS: &[&str] = &[
+    "a", "abbr", "address", "area", "article", "aside", "audio", "b", "base", "bdi", "bdo", "blockquote", "body", "br",
+    "button", "canvas", "caption", "cite", "code", "col", "colgroup", "data", "datalist", "dd", "del", "details", "dfn",
+    "dialog", "div", "dl", "dt", "em", "embed", "fieldset", "figcaption", "figure", "font", "footer", "form", "h1", "h2",
+    "h3", "h4", "h5", "h6", "head", "header", "hgroup", "hr", "html", "i", "iframe", "img", "input", "ins", "kbd",
+    "label", "legend", "li", "link", "main", "map", "mark", "math", "menu", "meta", "meter", "nav", "noscript", "object",
+    "ol", "optgroup", "option", "output", "p", "picture", "pre", "progress", "q", "rp", "rt", "ruby", "s", "samp",
+    "script", "search", "section", "select", "slot", "small", "source", "span", "strong", "style", "sub", "summary",
+    "sup", "svg", "table", "tbody", "td", "template", "textarea", "tfoot", "th", "thead", "time", "title", "tr",
+    "track", "tt", "u", "ul", "var", "video", "wbr",
+];
+
+/// 字符引用，如 `&copy;`、`&#20013;`
+static CHARACTER_REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"&(?:[A-Za-z][A-Za-z0-9]*|#[0-9]+|#[xX][0-9A-Fa-f]+);").unwrap()
+});
+
+/// A token of the input, with the byte range it was produced from.
+#[derive(Debug, Clone, PartialEq)]
+enum Chunk {
+    /// Character data, including the content of `<script>`, `<style>` and other
+    /// raw text elements
+    Text,
+    StartTag(String),
+    EndTag(String),
+    Comment,
+    /// Doctypes, incomplete tags and anything else the tokenizer dropped
+    Other,
+}
 
-/// process string or text
-fn process_text(pair: Pair<Rule>) -> String {
-    let mut result = String::new();
+/// Collects the kind of every token the tokenizer emits.
+#[derive(Default)]
+struct Sink {
+    emitted: Vec<Chunk>,
+}
 
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::single_quoted_string | Rule::double_quoted_string => {
-                result.push_str(&spacing(inner_pair.as_str()));
-            }
-            _ => {
-                result.push_str(inner_pair.as_str());
+impl TokenSink for Sink {
+    type Handle = ();
+
+    fn process_token(&mut self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            Token::CharacterTokens(_) | Token::NullCharacterToken => self.emitted.push(Chunk::Text),
+            Token::TagToken(tag) if tag.kind == TagKind::StartTag => {
+                let name = tag.name.to_string();
+                // The tree builder normally switches the tokenizer into these
+                // states; without one the sink has to do it
+                let result = match name.as_str() {
+                    "script" => TokenSinkResult::RawData(RawKind::ScriptData),
+                    "style" | "xmp" | "iframe" | "noembed" | "noframes" => {
+                        TokenSinkResult::RawData(RawKind::Rawtext)
+                    }
+                    "title" | "textarea" => TokenSinkResult::RawData(RawKind::Rcdata),
+                    "plaintext" => TokenSinkResult::Plaintext,
+                    _ => TokenSinkResult::Continue,
+                };
+                self.emitted.push(Chunk::StartTag(name));
+                return result;
             }
+            Token::TagToken(tag) => self.emitted.push(Chunk::EndTag(tag.name.to_string())),
+            Token::CommentToken(_) => self.emitted.push(Chunk::Comment),
+            Token::DoctypeToken(_) => self.emitted.push(Chunk::Other),
+            Token::EOFToken | Token::ParseError(_) => {}
         }
+        TokenSinkResult::Continue
     }
-
-    result
 }
 
-/// process attribute part, eg. class="...", id="..."
-fn process_attribute(pair: Pair<Rule>) -> String {
-    let mut result = String::new();
+/// Splits `input` into contiguous chunks covering all of it.
+///
+/// html5ever does not report source positions, so the input is fed in pieces
+/// split around every `<` and after every `>`. Tags, comments and doctypes are
+/// only emitted at a `>` or at the end, so each token is attributed to the end
+/// of the piece that made the tokenizer emit it.
+fn chunks(input: &str) -> Vec<(Range<usize>, Chunk)> {
+    let mut chunks: Vec<(Range<usize>, Chunk)> = Vec::new();
+    let mut cursor = 0;
+    let mut push = |chunks: &mut Vec<(Range<usize>, Chunk)>, end: usize, chunk: Chunk| {
+        if end <= cursor {
+            return;
+        }
+        match chunks.last_mut() {
+            Some((range, Chunk::Text)) if chunk == Chunk::Text => range.end = end,
+            _ => chunks.push((cursor..end, chunk)),
+        }
+        cursor = end;
+    };
 
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::attribute_value => {
-                let mut inner = inner_pair.into_inner();
-                let value = inner.next().expect("Attribute must have a value");
-                result.push_str(&process_text(value));
-            }
-            _ => {
-                result.push_str(inner_pair.as_str());
-            }
+    let mut tokenizer = Tokenizer::new(Sink::default(), TokenizerOpts::default());
+    let mut queue = BufferQueue::new();
+    let mut start = 0;
+    let ends = input.match_indices(['<', '>']).flat_map(|(i, c)| if c == "<" { [i, i + 1] } else { [i + 1; 2] });
+    for end in ends.chain([input.len()]) {
+        if end <= start {
+            continue;
+        }
+        queue.push_back(StrTendril::from_slice(&input[start..end]));
+        let _ = tokenizer.feed(&mut queue);
+        for chunk in std::mem::take(&mut tokenizer.sink.emitted) {
+            // Text flushed by a `<` ends before it: the `<` starts a tag
+            let end = if &input[start..end] == "<" { start } else { end };
+            push(&mut chunks, end, chunk);
         }
+        start = end;
     }
-
-    result
+    tokenizer.end();
+    for chunk in std::mem::take(&mut tokenizer.sink.emitted) {
+        push(&mut chunks, input.len(), chunk);
+    }
+    push(&mut chunks, input.len(), Chunk::Other);
+    chunks
 }
 
-/// process tag part, eg. <img src="" />, <div class="..." />
-fn process_tag(pair: Pair<Rule>) -> String {
-    let mut result = String::new();
+/// Value ranges of the attributes of a start tag, relative to `tag`.
+fn attributes(tag: &str) -> Vec<(&str, Range<usize>)> {
+    let bytes = tag.as_bytes();
+    let is_space = |b: u8| b.is_ascii_whitespace();
+    let mut attributes = Vec::new();
 
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::tag_name => {
-                result.push_str(inner_pair.as_str());
-            }
-            Rule::attribute => {
-                result.push_str(&process_attribute(inner_pair));
+    // Skip `<` and the tag name
+    let mut i = 1;
+    while i < bytes.len() && !is_space(bytes[i]) && bytes[i] != b'/' && bytes[i] != b'>' {
+        i += 1;
+    }
+    loop {
+        while i < bytes.len() && (is_space(bytes[i]) || bytes[i] == b'/') {
+            i += 1;
+        }
+        if i >= bytes.len() || bytes[i] == b'>' {
+            break;
+        }
+        let name_start = i;
+        i += 1;
+        while i < bytes.len() && !is_space(bytes[i]) && !b"/>=".contains(&bytes[i]) {
+            i += 1;
+        }
+        let name = &tag[name_start..i];
+        while i < bytes.len() && is_space(bytes[i]) {
+            i += 1;
+        }
+        if i >= bytes.len() || bytes[i] != b'=' {
+            continue;
+        }
+        i += 1;
+        while i < bytes.len() && is_space(bytes[i]) {
+            i += 1;
+        }
+        let value = match bytes.get(i) {
+            Some(&quote @ (b'"' | b'\'')) => {
+                let Some(len) = tag[i + 1..].find(quote as char) else {
+                    break;
+                };
+                i + 1..i + 1 + len
             }
             _ => {
-                result.push_str(inner_pair.as_str());
+                let start = i;
+                while i < bytes.len() && !is_space(bytes[i]) && bytes[i] != b'>' {
+                    i += 1;
+                }
+                start..i
             }
-        }
+        };
+        i = value.end + 1;
+        attributes.push((name, value));
+    }
+    attributes
+}
+
+/// Pushes text at `start`, leaving character references out as code
+fn push_text(segments: &mut Segments, text: &str, start: usize, markup: Markup) {
+    let mut pos = 0;
+    for reference in CHARACTER_REFERENCE.find_iter(text) {
+        segments.push_segment(Segment::new(SegmentKind::Text, start + pos..start + reference.start()).with_markup(markup));
+        pos = reference.end();
     }
+    segments.push_segment(Segment::new(SegmentKind::Text, start + pos..start + text.len()).with_markup(markup));
+}
 
-    result
+/// Whether the attribute `name` of an element is displayed text. `value` is
+/// only the caption of buttons; elsewhere it is data submitted with a form.
+fn is_spaced(element: &str, attributes: &[(&str, &str)], name: &str) -> bool {
+    if name.eq_ignore_ascii_case("value") {
+        let is_button = |(name, value): &(&str, &str)| {
+            name.eq_ignore_ascii_case("type") && ["button", "submit", "reset"].iter().any(|t| t.eq_ignore_ascii_case(value))
+        };
+        return element == "input" && attributes.iter().any(is_button);
+    }
+    SPACED_ATTRIBUTES.iter().any(|spaced| spaced.eq_ignore_ascii_case(name))
 }
 
-/// process void element part, eg. <img src="" />
-fn process_void_element(pair: Pair<Rule>) -> String {
-    let mut result = String::new();
+/// Whether a `<script>` start tag holds JavaScript, by its `type`
+fn is_javascript(tag: &str) -> bool {
+    attributes(tag)
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("type"))
+        .is_none_or(|(_, value)| {
+            let mime = tag[value].trim().to_ascii_lowercase();
+            ["", "module", "text/javascript", "application/javascript", "text/ecmascript", "application/ecmascript"]
+                .contains(&mime.as_str())
+        })
+}
 
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::attribute => {
-                result.push_str(&process_attribute(inner_pair));
-            }
-            _ => {
-                result.push_str(inner_pair.as_str());
-            }
+/// The kind of element `name`, by how browsers render it by default
+fn element(name: &str) -> Element {
+    match name {
+        "br" => Element::LineBreak,
+        "code" | "kbd" | "samp" | "tt" => Element::Code,
+        _ if INLINE_ELEMENTS.contains(&name) => Element::Inline,
+        _ => Element::Block,
+    }
+}
+
+/// Pushes a tag at `start`, with the values of the user-visible attributes of
+/// a start tag as text
+fn push_tag(segments: &mut Segments, tag: &str, start: usize, name: &str, open: bool) {
+    let kind = SegmentKind::Tag { element: element(name), open };
+    let attributes = if open { attributes(tag) } else { Vec::new() };
+    let values: Vec<(&str, &str)> = attributes.iter().map(|(name, value)| (*name, &tag[value.clone()])).collect();
+    let mut pos = 0;
+    for (attribute, value) in attributes {
+        if is_spaced(name, &values, attribute) {
+            segments.push(kind, start + pos..start + value.start);
+            push_text(segments, &tag[value.clone()], start + value.start, Markup::Html { tags: false });
+            pos = value.end;
         }
     }
+    segments.push(kind, start + pos..start + tag.len());
+}
 
-    result
+/// Segments of one piece of inline HTML in Markdown, such as `<a title="提示">`
+/// or `</b>`, lexed like the tags of a document. `None` if it is the tag of no
+/// HTML element, like the `<T>` of `Vec<T>`, which belongs to the text around it.
+pub(crate) fn inline_tag(source: &str) -> Option<Vec<Segment>> {
+    let closing = source.starts_with("</");
+    let name = source[if closing { 2 } else { 1 }.min(source.len())..]
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if !source.starts_with('<') || !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        // Declarations and processing instructions
+        return Some(vec![Segment::new(SegmentKind::Code, 0..source.len())]);
+    }
+    // Custom elements have a `-` in their names
+    if !ELEMENTS.contains(&name.as_str()) && !name.contains('-') {
+        return None;
+    }
+    let mut segments = Segments::new();
+    push_tag(&mut segments, source, 0, &name, !closing);
+    Some(segments.finish(source.len()))
 }
 
-/// HTML处理函数，使用pest解析HTML并应用spacing
+/// HTML处理函数，使用 html5ever 切分输入，只修改文本和部分属性值，其余内容原样保留
 pub fn process(input: &str) -> Result<String> {
-    let pairs = match HtmlParser::parse(Rule::html, input) {
-        Ok(p) => p,
-        Err(e) => {
-            return Err(e.into());
-        }
-    };
+    process_with_options(input, &SpacingOptions::default())
+}
 
-    let mut result = Vec::new();
+pub fn process_with_options(input: &str, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(input, &segment(input)?))
+}
 
-    fn parse_pair(result: &mut Vec<String>, pair: Pair<Rule>, input: &str) {
-        match pair.as_rule() {
-            // 1. Recursive descent for structure rules:
-            Rule::html | Rule::content | Rule::element => {
-                for inner_pair in pair.into_inner() {
-                    parse_pair(result, inner_pair, input);
-                }
-            }
-            // 2. Apply spacing to specific content rules:
-            Rule::COMMENT => {
-                result.push("<!--".to_string());
-                let inner = pair
-                    .into_inner()
-                    .into_iter()
-                    .map(|p| p.as_str())
-                    .collect::<Vec<_>>();
-                let new_html = process(&inner.join(""));
-                result.push(new_html.unwrap());
-                result.push("-->".to_string());
-            }
-            Rule::text => {
-                let spaced_text = spacing(pair.as_str());
-                result.push(spaced_text);
-            }
-            Rule::html_entity => result.push(pair.as_str().to_string()),
-            // 3. Reconstruct tags/attributes without internal spacing (but spacing attribute values):
-            Rule::left_tag => result.push(process_tag(pair)),
-            Rule::right_tag => result.push(pair.as_str().to_string()),
-            // Handle void elements (like <input>)
-            Rule::void_element => result.push(process_void_element(pair)),
-            Rule::script_tag => {
-                for inner_pair in pair.into_inner() {
-                    match inner_pair.as_rule() {
-                        Rule::script_content => {
-                            result.push(js::process(inner_pair.as_str()).unwrap())
-                        }
-                        _ => result.push(inner_pair.as_str().to_string()),
+/// Malformed markup is tokenized the way browsers do and never rejected, but
+/// an embedded script or style that fails to parse is an error, located in
+/// `input`. Scripts of other types than JavaScript are kept as they are.
+pub(crate) fn segment(input: &str) -> Result<Vec<Segment>> {
+    let mut segments = Segments::new();
+    // The element whose raw text the next text chunk is; empty for scripts
+    // that are not JavaScript, such as templates or JSON data
+    let mut raw_text: Option<String> = None;
+
+    for (range, chunk) in chunks(input) {
+        let source = &input[range.clone()];
+        let start = range.start;
+        match chunk {
+            Chunk::Text => {
+                let embedded = match raw_text.as_deref() {
+                    Some("script") => Some(js::segment(source)),
+                    Some("style") => Some(css::segment(source)),
+                    _ => None,
+                };
+                match embedded {
+                    Some(embedded) => {
+                        let embedded = embedded.map_err(|e| ParseError::within(e, input, start))?;
+                        segments.extend(start, embedded, None)
                     }
-                }
-            }
-            Rule::style_tag => {
-                for inner_pair in pair.into_inner() {
-                    match inner_pair.as_rule() {
-                        Rule::style_content => {
-                            result.push(css::process(inner_pair.as_str()).unwrap())
-                        }
-                        _ => result.push(inner_pair.as_str().to_string()),
+                    None if raw_text.as_deref() == Some("") => {}
+                    // Escapable raw text cannot hold tags
+                    None => {
+                        let tags = !matches!(raw_text.as_deref(), Some("textarea" | "title"));
+                        push_text(&mut segments, source, start, Markup::Html { tags });
                     }
                 }
             }
-            _ => {
-                result.push(pair.as_str().to_string());
+            Chunk::StartTag(ref name) => {
+                push_tag(&mut segments, source, start, name, true);
+                raw_text = Some(if name == "script" && !is_javascript(source) { String::new() } else { name.clone() });
+                continue;
+            }
+            Chunk::Comment if source.len() >= 7 && source.starts_with("<!--") && source.ends_with("-->") => {
+                // Commented-out markup is processed like any other
+                let body = &source[4..source.len() - 3];
+                let kind = SegmentKind::Comment { style: CommentStyle::Html };
+                let body = segment(body).map_err(|e| ParseError::within(e, input, start + 4))?;
+                segments.extend(start + 4, body, Some(kind));
             }
+            Chunk::EndTag(ref name) => push_tag(&mut segments, source, start, name, false),
+            Chunk::Comment | Chunk::Other => {}
         }
+        raw_text = None;
     }
 
-    for pair in pairs {
-        parse_pair(&mut result, pair, input);
-    }
-
-    Ok(result.join(""))
+    Ok(segments.finish(input.len()))
 }
 
 #[cfg(test)]
@@ -204,10 +393,24 @@ mod tests {
         assert_eq!(process(input).unwrap(), expected);
 
         // 测试单引号字符串
-        let input = "<input value='这是值Value'>";
-        let expected = "<input value='这是值 Value'>";
+        let input = "<input type='submit' value='这是值Value'>";
+        let expected = "<input type='submit' value='这是值 Value'>";
         assert_eq!(process(input).unwrap(), expected);
 
+        // 表单提交的值不修改
+        for input in [
+            "<input value=\"中文English\">",
+            "<input type=\"text\" value=\"中文English\">",
+            "<option value=\"中文English\">选项Option</option>",
+            "<button value=\"中文English\">按钮</button>",
+        ] {
+            assert!(process(input).unwrap().contains("value=\"中文English\""), "{}", input);
+        }
+        assert_eq!(
+            process("<input VALUE=按钮Button Type=RESET>").unwrap(),
+            "<input VALUE=按钮 Button Type=RESET>"
+        );
+
         // 测试包含特殊字符的字符串
         let input = "<div title=\"包含'单引号'和\\反斜杠\">内容Content</div>";
         let expected = "<div title=\"包含'单引号'和\\反斜杠\">内容 Content</div>";
@@ -273,6 +476,38 @@ mod tests {
         assert_eq!(process(input).unwrap(), expected);
     }
 
+    #[test]
+    fn test_inline_boundaries() {
+        let cases = [
+            ("<p>中文<a href=\"#\">Link</a>文字</p>", "<p>中文 <a href=\"#\">Link</a> 文字</p>"),
+            ("<p>中文<b><i>Bold</i></b>文字</p>", "<p>中文 <b><i>Bold</i></b> 文字</p>"),
+            ("<p><a title=\"提示\">中文</a>English</p>", "<p><a title=\"提示\">中文</a> English</p>"),
+            ("<p>中文<code>code</code>文字</p>", "<p>中文 <code>code</code> 文字</p>"),
+            // Block elements and line breaks separate the text
+            ("<div>中文</div><div>English</div>", "<div>中文</div><div>English</div>"),
+            ("<p>中文<br>English</p>", "<p>中文<br>English</p>"),
+            ("<p>中文 <a>Link</a> 文字</p>", "<p>中文 <a>Link</a> 文字</p>"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(process(input).unwrap(), expected);
+        }
+
+        let options = SpacingOptions::new().with_separators([Element::Block, Element::Code]);
+        let input = "<p>中文<code>code</code>文字<br>English</p>";
+        assert_eq!(process_with_options(input, &options).unwrap(), "<p>中文<code>code</code>文字 <br>English</p>");
+    }
+
+    #[test]
+    fn test_script_types() {
+        // Scripts of other types are not JavaScript, and kept as they are
+        let input = "<script type=\"text/template\"><p>{{ 模板abc }}</p></script>";
+        assert_eq!(process(input).unwrap(), input);
+        let input = "<script type=\"application/json\">{\"键\": \"值abc\"}</script>";
+        assert_eq!(process(input).unwrap(), input);
+        let input = "<script type=\"module\">let s = '中文abc';</script>";
+        assert_eq!(process(input).unwrap(), "<script type=\"module\">let s = '中文 abc';</script>");
+    }
+
     #[test]
     fn test_mixed_content() {
         // 测试混合内容（注释、标签、文本）
@@ -288,18 +523,15 @@ mod tests {
 
     #[test]
     fn test_special_cases() {
-        // Test incomplete tags - should result in parsing errors
+        // Test incomplete tags - kept as they are
         let input1 = "<div";
-        assert!(
-            process(input1).is_err(),
-            "Incomplete opening tag should fail"
-        );
+        assert_eq!(process(input1).unwrap(), input1);
 
         let input2 = "</div";
-        assert!(
-            process(input2).is_err(),
-            "Incomplete closing tag should fail"
-        );
+        assert_eq!(process(input2).unwrap(), input2);
+
+        let input = "文本Text<div title=\"标题Title";
+        assert_eq!(process(input).unwrap(), "文本 Text<div title=\"标题Title");
 
         // Test unclosed tag
         let input3 = "<div>test";
@@ -336,4 +568,69 @@ mod tests {
         let expected = "<!DOCTYPE html>\n<html>\n<head>\n<title>PHP 语法综合示例</title>\n</head>\n<body>";
         assert_eq!(process(input).unwrap(), expected);
     }
+
+    #[test]
+    fn test_real_world_markup() {
+        // Uppercase tags and lowercase doctype
+        let input = "<!doctype html>\n<P CLASS=\"x\">段落Text</P>";
+        let expected = "<!doctype html>\n<P CLASS=\"x\">段落 Text</P>";
+        assert_eq!(process(input).unwrap(), expected);
+
+        // Framework attributes and unquoted values containing `/`
+        let input = "<a data-x.y=\"1\" @click=\"go('路径a')\" href=/a/b/c :title='标题Title'>链接Link</a>";
+        let expected = "<a data-x.y=\"1\" @click=\"go('路径a')\" href=/a/b/c :title='标题Title'>链接 Link</a>";
+        assert_eq!(process(input).unwrap(), expected);
+
+        // Optional end tags
+        let input = "<ul><li>第一First<li>第二Second</ul><p>段落One<p>段落Two";
+        let expected = "<ul><li>第一 First<li>第二 Second</ul><p>段落 One<p>段落 Two";
+        assert_eq!(process(input).unwrap(), expected);
+
+        // CDATA is kept as it is
+        let input = "<svg><![CDATA[数据Data]]></svg><p>文本Text</p>";
+        let expected = "<svg><![CDATA[数据Data]]></svg><p>文本 Text</p>";
+        assert_eq!(process(input).unwrap(), expected);
+
+        // Stray `<` and `&`, CRLF line endings
+        let input = "<p>a < b中文\r\n&c中文 && 1<2</p>";
+        let expected = "<p>a < b 中文\r\n&c 中文 && 1<2</p>";
+        assert_eq!(process(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let input = "<img ALT=标题Title src=\"图片a.png\"><input placeholder = '输入Input' value>";
+        let expected = "<img ALT=标题 Title src=\"图片a.png\"><input placeholder = '输入 Input' value>";
+        assert_eq!(process(input).unwrap(), expected);
+
+        let input = "<meta name=\"description\" content=\"描述Description\"><span class=\"类名Class\"></span>";
+        let expected = "<meta name=\"description\" content=\"描述 Description\"><span class=\"类名Class\"></span>";
+        assert_eq!(process(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let chunks = |input| chunks(input).into_iter().map(|(range, chunk)| (&input[range], chunk)).collect::<Vec<_>>();
+        // A `>` in an attribute value does not end the tag
+        assert_eq!(
+            chunks("<a title=\"a>b\">文本</a>"),
+            [("<a title=\"a>b\">", Chunk::StartTag("a".into())), ("文本", Chunk::Text), ("</a>", Chunk::EndTag("a".into()))]
+        );
+        // A `<` starting no tag is text, and CRLF stays in the text it ends
+        assert_eq!(chunks("a < b\r\n<br>"), [("a < b\r\n", Chunk::Text), ("<br>", Chunk::StartTag("br".into()))]);
+        assert_eq!(chunks("<<!x>"), [("<", Chunk::Text), ("<!x>", Chunk::Comment)]);
+    }
+
+    #[test]
+    fn test_raw_text_elements() {
+        // Markup-like content of raw text elements is not tokenized as tags
+        let input = "<textarea>文本Text<b></textarea><title>标题Title</title>";
+        let expected = "<textarea>文本 Text<b></textarea><title>标题 Title</title>";
+        assert_eq!(process(input).unwrap(), expected);
+
+        // A `<` in a script starts no tag
+        let input = "<SCRIPT>if (a < b) { x = `模板Template` }</SCRIPT><p>段落Text</p>";
+        let result = process(input).unwrap();
+        assert!(result.ends_with("<p>段落 Text</p>"));
+    }
 }
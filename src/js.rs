@@ -2,105 +2,78 @@
 use anyhow::Result;
 use pest::Parser;
 use pest::iterators::Pair;
-// Import the spacing function from the crate root
-use crate::spacing;
+use crate::{ParseError, SpacingOptions};
+use crate::edit::Context;
+use crate::segment::{CommentStyle, Segment, SegmentKind, Segments};
 
 #[derive(pest_derive::Parser)]
 #[grammar = "grammar/js.pest"] // Path relative to src
 pub struct JsParser;
 
 pub fn process(input: &str) -> Result<String> {
-    let pairs = match JsParser::parse(Rule::program, input) {
-        Ok(p) => p,
-        Err(e) => {
-            println!("Pest parsing error:\n{}", e);
-            return Err(e.into());
-        }
-    };
-    let mut result = String::with_capacity(input.len());
+    process_with_options(input, &SpacingOptions::default())
+}
+
+pub fn process_with_options(input: &str, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(input, &segment(input)?))
+}
 
-    fn parse_pair(result: &mut String, pair: Pair<Rule>) {
+pub(crate) fn segment(input: &str) -> Result<Vec<Segment>> {
+    let pairs = JsParser::parse(Rule::program, input).map_err(ParseError::from)?;
+    let mut segments = Segments::new();
+
+    fn parse_pair(segments: &mut Segments, pair: Pair<Rule>) {
         match pair.as_rule() {
             Rule::comment | Rule::string => {
                 for inner_pair in pair.into_inner() {
-                    parse_pair(result, inner_pair);
+                    parse_pair(segments, inner_pair);
                 }
             }
             Rule::line_comment => {
-                let content_pairs: Vec<_> = pair.into_inner().collect();
-                let content = content_pairs.iter().map(|p| p.as_str()).collect::<String>();
-                let spaced_content = spacing(&content);
-                result.push_str(&format!("//{}", spaced_content));
+                let span = pair.as_span();
+                segments.push(SegmentKind::Comment { style: CommentStyle::Line }, span.start() + 2..span.end());
             }
             Rule::block_comment => {
-                let content_pairs: Vec<_> = pair.into_inner().collect();
-                let content = content_pairs.iter().map(|p| p.as_str()).collect::<String>();
-                let spaced_content = spacing(&content);
-                result.push_str(&format!("/*{}*/", spaced_content));
-            }
-            Rule::double_quoted_string | Rule::single_quoted_string | Rule::template_literal => {
-                let quote_char = match pair.as_rule() {
-                    Rule::double_quoted_string => '"',
-                    Rule::single_quoted_string => '\'',
-                    Rule::template_literal => '`',
-                    _ => unreachable!(),
-                };
-
-                if pair.as_rule() == Rule::template_literal {
-                    result.push('`'); // Start template literal
-
-                    let mut current_literal_chunk = String::new();
-                    // Find the content pair first. Clone pair as into_inner consumes it.
-                    let content_pair_opt = pair.clone().into_inner().find(|p| p.as_rule() == Rule::template_literal_content);
-                    
-                    if let Some(content_pair) = content_pair_opt {
-                         for inner_pair in content_pair.into_inner() {
-                            match inner_pair.as_rule() {
-                                Rule::template_expression => {
-                                    // Process the preceding literal chunk
-                                    if !current_literal_chunk.is_empty() {
-                                        result.push_str(&spacing(&current_literal_chunk));
-                                        current_literal_chunk.clear();
-                                    }
-                                    // Append the expression directly (no spacing)
-                                    result.push_str(inner_pair.as_str());
-                                }
-                                _ => {
-                                    // Append other chars (literal text, escapes) to the current chunk
-                                    current_literal_chunk.push_str(inner_pair.as_str());
-                                }
-                            }
-                        }
-                    } // else: Handle case with no template_literal_content?
-                   
-
-                    // Process any remaining literal chunk
-                    if !current_literal_chunk.is_empty() {
-                        result.push_str(&spacing(&current_literal_chunk));
-                    }
-
-                    result.push('`'); // End template literal
+                let span = pair.as_span();
+                let content = &pair.as_str()[2..pair.as_str().len() - 2];
+                // `/** ... */` is a JSDoc comment, but `/**/` is just empty
+                let style = if content.starts_with('*') && content != "*" {
+                    CommentStyle::Doc
                 } else {
-                    // Original handling for double/single quotes
-                    // Assume content is the first (and only) inner pair based on grammar
-                    let content = pair.into_inner().next().map(|p| p.as_str()).unwrap_or(""); // Get content str
-                    let spaced_content = spacing(content);
-                    result.push(quote_char);
-                    result.push_str(&spaced_content);
-                    result.push(quote_char);
+                    CommentStyle::Block
+                };
+                segments.push(SegmentKind::Comment { style }, span.start() + 2..span.end() - 2);
+            }
+            Rule::double_quoted_string | Rule::single_quoted_string => {
+                let quote = if pair.as_rule() == Rule::double_quoted_string { '"' } else { '\'' };
+                // The content is the only inner pair
+                if let Some(content) = pair.into_inner().next() {
+                    let span = content.as_span();
+                    segments.push(SegmentKind::StringLiteral { quote: Some(quote) }, span.start()..span.end());
                 }
             }
-            _ => {
-                result.push_str(pair.as_str());
+            Rule::template_literal => {
+                let kind = SegmentKind::StringLiteral { quote: Some('`') };
+                let content = pair.into_inner().find(|p| p.as_rule() == Rule::template_literal_content);
+                // Literal text between expressions, which are left as code
+                for inner_pair in content.into_iter().flat_map(|p| p.into_inner()) {
+                    let is_expression = inner_pair.clone().into_inner().next().map(|p| p.as_rule())
+                        == Some(Rule::template_expression);
+                    if !is_expression {
+                        let span = inner_pair.as_span();
+                        segments.push(kind, span.start()..span.end());
+                    }
+                }
             }
+            _ => {}
         }
     }
 
     for pair in pairs {
-        parse_pair(&mut result, pair);
+        parse_pair(&mut segments, pair);
     }
 
-    Ok(result)
+    Ok(segments.finish(input.len()))
 }
 
 #[cfg(test)]
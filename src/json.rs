@@ -1,7 +1,9 @@
 use pest::Parser;
 use pest_derive::Parser;
 
-use crate::spacing;
+use crate::{ParseError, SpacingOptions};
+use crate::edit::Context;
+use crate::segment::{Segment, SegmentKind, Segments};
 use anyhow::Result;
 use pest::iterators::Pair;
 
@@ -11,23 +13,34 @@ use pest::iterators::Pair;
 struct JSONParser;
 
 pub fn process(input: &str) -> Result<String> {
-    let r = JSONParser::parse(Rule::json, input)?;
-    let mut result: Vec<String> = Vec::new();
-    fn parse_value(result: &mut Vec<String>, pair: Pair<Rule>) {
+    process_with_options(input, &SpacingOptions::default())
+}
+
+pub fn process_with_options(input: &str, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(input, &segment(input)?))
+}
+
+pub(crate) fn segment(input: &str) -> Result<Vec<Segment>> {
+    let r = JSONParser::parse(Rule::json, input).map_err(ParseError::from)?;
+    let mut segments = Segments::new();
+    fn parse_value(segments: &mut Segments, pair: Pair<Rule>) {
         match pair.as_rule() {
             Rule::object | Rule::array => {
                 for p in pair.into_inner() {
-                    parse_value(result, p);
+                    parse_value(segments, p);
                 }
             }
-            Rule::string => result.push(spacing(pair.as_str())),
-            _ => result.push(pair.as_str().to_owned()),
+            Rule::string => {
+                let span = pair.as_span();
+                segments.push(SegmentKind::StringLiteral { quote: Some('"') }, span.start() + 1..span.end() - 1);
+            }
+            _ => {}
         };
     }
     for pair in r {
-        parse_value(&mut result, pair);
+        parse_value(&mut segments, pair);
     }
-    Ok(result.join(""))
+    Ok(segments.finish(input.len()))
 }
 
 #[cfg(test)]
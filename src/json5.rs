@@ -4,7 +4,9 @@ use pest_derive::Parser;
 
 use anyhow::Result;
 
-use crate::spacing;
+use crate::{ParseError, SpacingOptions};
+use crate::edit::Context;
+use crate::segment::{CommentStyle, Segment, SegmentKind, Segments};
 
 #[allow(dead_code)]
 #[derive(Parser)]
@@ -12,64 +14,48 @@ use crate::spacing;
 struct JSON5Parser;
 
 pub fn process(input: &str) -> Result<String> {
-    let pairs = JSON5Parser::parse(Rule::json, input)?;
-    let mut result: Vec<String> = Vec::new();
+    process_with_options(input, &SpacingOptions::default())
+}
+
+pub fn process_with_options(input: &str, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(input, &segment(input)?))
+}
+
+pub(crate) fn segment(input: &str) -> Result<Vec<Segment>> {
+    let pairs = JSON5Parser::parse(Rule::json, input).map_err(ParseError::from)?;
+    let mut segments = Segments::new();
 
     // Helper function to recursively process pairs
-    fn parse_pair(result: &mut Vec<String>, pair: Pair<Rule>) {
+    fn parse_pair(segments: &mut Segments, pair: Pair<Rule>) {
+        let (start, end) = (pair.as_span().start(), pair.as_span().end());
         match pair.as_rule() {
-            // Handle rules that need spacing
-            Rule::string => {
-                let s = pair.as_str();
-                if s.len() >= 2 {
-                    let quote = &s[0..1]; // " or '
-                    let content = &s[1..s.len() - 1];
-                    // Apply spacing to the inner content
-                    let spaced_content = spacing(content);
-                    // Reconstruct the string with original quotes
-                    result.push(format!("{}{}{}", quote, spaced_content, quote));
-                } else {
-                    // Handle potentially empty or malformed strings
-                    result.push(s.to_owned());
-                }
+            // The content of strings, without the quotes
+            Rule::string if pair.as_str().len() >= 2 => {
+                let quote = pair.as_str().chars().next(); // " or '
+                segments.push(SegmentKind::StringLiteral { quote }, start + 1..end - 1);
             }
             Rule::LINE_COMMENT => {
-                let content = pair
-                    .into_inner()
-                    .map(|p| p.as_str())
-                    .collect::<Vec<&str>>()
-                    .join("");
-                result.push(format!("//{}", spacing(&content)));
+                segments.push(SegmentKind::Comment { style: CommentStyle::Line }, start + 2..end);
             }
             Rule::BLOCK_COMMENT => {
-                let content = pair
-                    .into_inner()
-                    .map(|p| p.as_str())
-                    .collect::<Vec<&str>>()
-                    .join("");
-                result.push(format!("/*{}*/", spacing(&content)));
+                segments.push(SegmentKind::Comment { style: CommentStyle::Block }, start + 2..end - 2);
             }
             // Handle rules that contain nested structures; recurse into them
             Rule::json | Rule::value | Rule::object | Rule::array | Rule::pair | Rule::COMMENT => {
                 for inner_pair in pair.into_inner() {
-                    parse_pair(result, inner_pair);
+                    parse_pair(segments, inner_pair);
                 }
             }
-            // Catch-all for any unexpected rules encountered
-            _ => {
-                // For debugging, print unexpected rules
-                // println!("Unhandled rule: {:?}", pair.as_rule());
-                // Default behavior: append the text representation
-                result.push(pair.as_str().to_owned());
-            }
+            // Everything else is kept as it is
+            _ => {}
         }
     }
 
     for pair in pairs {
-        parse_pair(&mut result, pair);
+        parse_pair(&mut segments, pair);
     }
 
-    Ok(result.join(""))
+    Ok(segments.finish(input.len()))
 }
 
 #[cfg(test)]
@@ -214,7 +200,7 @@ mod tests {
 
         // Example: Invalid syntax (e.g., colon instead of comma)
         let json5_invalid = r#"{ "a": 1, "b": 2, }"#;
-        assert!(!process(json5_invalid).is_err());
+        assert!(process(json5_invalid).is_ok());
     }
 
     #[test]
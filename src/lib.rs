@@ -1,27 +1,64 @@
-#[macro_use]
-extern crate pest_derive;
-
-use std::collections::VecDeque;
-use unicode_width::UnicodeWidthChar;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::LazyLock;
 
 // 声明模块
+mod char_class;
+// The binary's entry point, also for programs with their own formats
+// registered; not part of the library API
+#[doc(hidden)]
+pub mod cli;
+mod config;
+mod directive;
+mod edit;
+mod error;
+mod format;
+mod hangul;
 mod html;
 mod json;
 mod json5;
 mod js; // Add js module declaration
 mod markdown;
+mod options;
+mod processor;
+mod segment;
+mod stream;
+mod whitespace;
+mod width;
 mod rust;
+mod tokens;
 mod css;
 mod php; // Add php module declaration
 // Re-export 主要函数
 pub use html::process as process_html;
+pub use html::process_with_options as process_html_with_options;
 pub use markdown::process as process_markdown;
+pub use markdown::process_with_options as process_markdown_with_options;
 pub use css::process as process_css;
+pub use css::process_with_options as process_css_with_options;
 pub use js::process as process_js;
+pub use js::process_with_options as process_js_with_options;
 pub use rust::process as process_rust;
+pub use rust::process_with_options as process_rust_with_options;
 pub use json::process as process_json;
+pub use json::process_with_options as process_json_with_options;
 pub use json5::process as process_json5;
+pub use json5::process_with_options as process_json5_with_options;
 pub use php::process as process_php;
+pub use php::process_with_options as process_php_with_options;
+pub use options::{Element, Lang, Scope, Spacer, SpacingOptions, SpacingRule};
+pub use edit::{Edit, analyze, analyze_with_options};
+pub use stream::{spacing_stream, spacing_stream_with_options};
+pub use format::{Format, process, process_with_options};
+pub use segment::{CommentStyle, Markup, Segment, SegmentKind, segments};
+pub use processor::{FormatProcessor, processor, processors, register};
+pub use char_class::CharClass;
+pub use error::ParseError;
+pub use config::{CONFIG_FILE_NAME, Config, options_to_toml};
+
+static DEFAULT_OPTIONS: LazyLock<SpacingOptions> = LazyLock::new(SpacingOptions::default);
+static UNSPACING_OPTIONS: LazyLock<SpacingOptions> =
+    LazyLock::new(|| SpacingOptions::default().with_unspacing(true));
 
 /// （在一定条件下）在全角和半角字符之间添加空格
 ///
@@ -33,153 +70,396 @@ pub use php::process as process_php;
 /// let text = "当你凝视着bug，bug也凝视着你";
 /// assert_eq!(spacing(text), "当你凝视着 bug，bug 也凝视着你");
 /// ```
-pub fn spacing(text: &str) -> String {
-    // 如果文本为空，直接返回空字符串
-    if text.is_empty() {
-        return String::new();
-    }
-    // println!("--- spacing called with: \"{}\" ---", text); // Start marker
-
-    // 处理转义字符
-    // let text = process_escape_sequences(text);
-
-    let mut origin: VecDeque<char> = text.chars().collect();
-    let mut result: Vec<char> = Vec::new();
-
-    let mut prev: Option<char> = None;
-    // let mut cur: Option<char> = None;
-    while let Some(cur_ch) = origin.pop_front() {
-        // println!("Loop start: prev={:?}, cur='{}'", prev, cur_ch.escape_debug()); // Debug print
-
-        match (prev, cur_ch) {
-            (Some(prev_ch), cur_ch) => {
-                let prev_ch_width = CharWidth::from_char(prev_ch);
-                let cur_ch_width = CharWidth::from_char(cur_ch);
-                // println!("  Prev width: {:?}, Cur width: {:?}", prev_ch_width, cur_ch_width); // Debug print
-
-                // case 0: prev is space
-                if prev_ch == ' ' {
-                    // println!("  Case 0: prev is space"); // Debug print
-                    result.push(cur_ch);
-                    prev = Some(cur_ch);
-                    continue;
-                }
-
-                // case 1: prev is full, cur is half
-                if prev_ch_width.is_full() && cur_ch_width.is_half() {
-                    // println!("  Case 1: prev=Full, cur=Half"); // Debug print
-                    // special case: 全角字符与半角标点之间不加空格, 全角标点与半角字符之间不加空格
-                    let special_pre_full = vec![
-                        '，', '。', '！', '？', '：', '；', '“', '”', '‘', '’', '《', '》', '【',
-                        '】', '（', '）', '—', '…', '～', '·', '、',
-                    ];
-                    let special_cur_half =
-                        vec![',', '.', '!', '?', ':', ';', '"', '\'', '\n', '\r', '\t', '\\']; // Added missing quote for vec definition
-
-                    // special case：货币符号后跟数字不加空格
-                    let is_currency_before_number =
-                        (prev_ch == '¥' || prev_ch == '€') && cur_ch.is_numeric();
-
-                    if cur_ch != ' '
-                        && !special_pre_full.contains(&prev_ch)
-                        && !special_cur_half.contains(&cur_ch)
-                        && !is_currency_before_number
-                    {
-                        // println!("    Adding space between Full and Half"); // Debug print
-                        result.push(' ');
-                    } else {
-                        // println!("    Skipping space between Full and Half (special case)"); // Debug print
-                    }
-
-                    result.push(cur_ch);
-                    prev = Some(cur_ch);
-                    continue;
-                }
-
-                // case 2: prev is half, cur is full
-                if prev_ch_width.is_half() && cur_ch_width.is_full() {
-                    // println!("  Case 2: prev=Half, cur=Full"); // Debug print
-                    // special case: 半角符号与全角字符不加空格，半角字符与全角标点间不加空格
-                    let special_pre_half = vec![
-                        '"', '\'', '[', '{', '<', '@', '#', '%', '^', '&',
-                        '_', '|', '\\',
-                    ];
-                    // 全角标点等特殊字符
-                    let special_cur_full = vec![
-                        '，', '。', '！', '？', '：', '；', '“', '”', '‘', '’', '《', '》', '【',
-                        '】', '（', '）', '—', '…',
-                    ];
-
-                    // special case: 货币符号后跟数字不加空格
-                    let is_currency_before_number =
-                        (prev_ch == '$' || prev_ch == '¥' || prev_ch == '€')
-                            && cur_ch_width.is_full()
-                            && !special_cur_full.contains(&cur_ch);
-
-                    // special case: 换行符不加空格
-                    let is_line_break = prev_ch == '\n';
-
-                    if !special_pre_half.contains(&prev_ch)
-                        && !special_cur_full.contains(&cur_ch)
-                        && !is_currency_before_number
-                        && !is_line_break
-                    {
-                        // println!("    Adding space between Half and Full"); // Debug print
-                        result.push(' ');
-                    } else {
-                        // println!("    Skipping space between Half and Full (special case)"); // Debug print
-                    }
-                    result.push(cur_ch);
-                    prev = Some(cur_ch);
-                    continue;
-                }
-
-                // other cases:
-                // prev is full, cur is full,
-                // prev is half, cur is half
-                // println!("  Other case: pushing cur"); // Debug print
-                result.push(cur_ch);
-                prev = Some(cur_ch);
-                continue;
+pub fn spacing(text: &str) -> Cow<'_, str> {
+    spacing_with_options(text, &DEFAULT_OPTIONS)
+}
+
+/// 按照给定的规则配置添加空格，见 [`SpacingOptions`]；配置了 [`SpacingOptions::with_unspacing`] 时去掉空格
+pub fn spacing_with_options<'a>(text: &'a str, options: &SpacingOptions) -> Cow<'a, str> {
+    transform(text, options, Scope::Text, options.spacer().as_str(), &[], |_, _, _| {})
+}
+
+/// `spacing` 的反向操作：去掉 `spacing` 会插入空格处已有的单个半角空格，其他空白保持不变
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::unspacing;
+///
+/// let text = "当你凝视着 bug，bug 也凝视着你  ";
+/// assert_eq!(unspacing(text), "当你凝视着bug，bug也凝视着你  ");
+/// ```
+pub fn unspacing(text: &str) -> Cow<'_, str> {
+    unspacing_with_options(text, &UNSPACING_OPTIONS)
+}
+
+/// 按照给定的规则配置去掉空格，只去掉这些规则会插入空格的位置
+pub fn unspacing_with_options<'a>(text: &'a str, options: &SpacingOptions) -> Cow<'a, str> {
+    unspacing_impl(text, options, Scope::Text, options.spacer().as_str(), &[], |_, _, _| {})
+}
+
+/// 带上下文地添加空格：`prev`、`next` 是与 `text` 相邻、但不属于 `text` 的字符，
+/// 如行内标记另一侧的文字；需要时在结果的开头或结尾加上间隔
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::spacing_with_context;
+///
+/// assert_eq!(spacing_with_context(Some('文'), "Link", Some('字')), " Link ");
+/// assert_eq!(spacing_with_context(None, "中文", Some('L')), "中文 ");
+/// assert_eq!(spacing_with_context(Some('a'), "b", None), "b");
+/// ```
+pub fn spacing_with_context(prev: Option<char>, text: &str, next: Option<char>) -> Cow<'_, str> {
+    spacing_with_context_and_options(prev, text, next, &DEFAULT_OPTIONS)
+}
+
+/// 按照给定的规则配置带上下文地添加或去掉空格，见 [`spacing_with_context`]
+pub fn spacing_with_context_and_options<'a>(
+    prev: Option<char>,
+    text: &'a str,
+    next: Option<char>,
+    options: &SpacingOptions,
+) -> Cow<'a, str> {
+    let edits = context_edits(prev, text, next, options, Scope::Text, options.spacer().as_str());
+    if edits.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len() + edits.len() * options.spacer().as_str().len());
+    let mut pos = 0;
+    for (range, inserted, _) in edits {
+        result.push_str(&text[pos..range.start]);
+        result.push_str(&inserted);
+        pos = range.end;
+    }
+    result.push_str(&text[pos..]);
+    Cow::Owned(result)
+}
+
+/// 在 `prev`、`text`、`next` 连起来的文字上运行 [`transform`]，返回落在 `text` 内（含两端）的修改，
+/// 位置以 `text` 为准；涉及上下文字符的修改不在 `text` 中，被丢弃
+pub(crate) fn context_edits(
+    prev: Option<char>,
+    text: &str,
+    next: Option<char>,
+    options: &SpacingOptions,
+    scope: Scope,
+    spacer: &str,
+) -> Vec<(Range<usize>, String, SpacingRule)> {
+    let start = prev.map_or(0, char::len_utf8);
+    let end = start + text.len();
+    let mut joined = String::with_capacity(end + 4);
+    joined.extend(prev);
+    joined.push_str(text);
+    joined.extend(next);
+    let mut edits = Vec::new();
+    transform(&joined, options, scope, spacer, &[], |range, inserted, rule| {
+        if start <= range.start && range.end <= end {
+            edits.push((range.start - start..range.end - start, inserted.to_string(), rule));
+        }
+    });
+    edits.sort_by_key(|(range, _, _)| (range.start, range.len()));
+    edits
+}
+
+/// 按配置添加或去掉空格，回调同 [`spacing_impl`]
+pub(crate) fn transform<'a>(
+    text: &'a str,
+    options: &SpacingOptions,
+    scope: Scope,
+    spacer: &str,
+    ignored: &[Range<usize>],
+    on_edit: impl FnMut(Range<usize>, &str, SpacingRule),
+) -> Cow<'a, str> {
+    if options.is_unspacing() {
+        unspacing_impl(text, options, scope, spacer, ignored, on_edit)
+    } else {
+        spacing_impl(text, options, scope, spacer, ignored, on_edit)
+    }
+}
+
+/// `spacing` 的实现，每处修改都以（`text` 中被替换的字节范围，替换成的内容，规则）回调一次，
+/// 插入空格时范围为空
+///
+/// `scope` 是 `text` 所在的内容范围，注释中连续的空白视为对齐，不做规整。
+/// `spacer` 是按输出格式写出的间隔，如 HTML 中的 `&thinsp;`。
+/// `ignored` 是被忽略指令排除的字节范围，范围内部及边界处都不插入空格。
+pub(crate) fn spacing_impl<'a>(
+    text: &'a str,
+    options: &SpacingOptions,
+    scope: Scope,
+    spacer: &str,
+    ignored: &[Range<usize>],
+    mut on_edit: impl FnMut(Range<usize>, &str, SpacingRule),
+) -> Cow<'a, str> {
+    // Every rule needs a CJK or full-width character
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+
+    // 例外词和 URL、路径等记号所占的字节范围，范围内部不插入空格
+    let mut protected: Vec<Range<usize>> = options
+        .exceptions()
+        .iter()
+        .flat_map(|word| text.match_indices(word.as_str()))
+        .map(|(start, word)| start..start + word.len())
+        .collect();
+    if options.is_enabled(SpacingRule::ProtectedTokens) {
+        protected.extend(tokens::protected_ranges(text));
+    }
+    let protected = merge_ranges(protected);
+    // The first protected range that ends after `offset`
+    let protected_at = |offset: usize| protected.get(protected.partition_point(|range| range.end <= offset));
+    let is_protected = |offset: usize| {
+        protected_at(offset).is_some_and(|range| range.start < offset)
+            || ignored.iter().any(|range| range.start <= offset && offset <= range.end)
+    };
+
+    // 需要替换的字符：换成全角的半角标点、换成半角的全角字母和数字，按偏移排序
+    let mut conversions: Vec<(Range<usize>, char, SpacingRule)> = Vec::new();
+    let char_range = |offset: usize| offset..offset + text[offset..].chars().next().map_or(0, char::len_utf8);
+    if options.is_enabled(SpacingRule::FullWidthPunctuation) {
+        conversions.extend(
+            width::full_width_punctuation(text)
+                .into_iter()
+                .map(|(offset, c)| (char_range(offset), c, SpacingRule::FullWidthPunctuation)),
+        );
+    }
+    if options.is_enabled(SpacingRule::HalfWidthAlphanumerics) {
+        conversions.extend(
+            width::half_width_alphanumerics(text)
+                .into_iter()
+                .map(|(offset, c)| (char_range(offset), c, SpacingRule::HalfWidthAlphanumerics)),
+        );
+    }
+    conversions.sort_by_key(|(range, _, _)| range.start);
+    let is_excluded = |range: &Range<usize>| {
+        protected_at(range.start).is_some_and(|protected| protected.start <= range.start)
+            || ignored.iter().any(|ignored| ignored.start < range.end && range.start < ignored.end)
+    };
+    let mut conversions = conversions
+        .into_iter()
+        .filter(|(range, _, _)| !is_excluded(range))
+        .peekable();
+
+    // 需要规整成一个间隔的空白
+    let mut runs = if options.is_enabled(SpacingRule::NormalizeSpaces) {
+        let keep_alignment = matches!(scope, Scope::Comments | Scope::DocComments);
+        whitespace::boundary_runs(text, keep_alignment)
+    } else {
+        Vec::new()
+    }
+    .into_iter()
+    .filter(|range| &text[range.clone()] != spacer && !is_excluded(range))
+    .peekable();
+
+    // 已有的间隔本身，如 `<span class="cjk-gap"></span>`，两侧都不再插入
+    let is_spacer_at = |offset: usize| !spacer.is_empty() && text[offset..].starts_with(spacer);
+    let follows_spacer = |offset: usize| !spacer.is_empty() && text[..offset].ends_with(spacer);
+
+    // 在 `offset` 处的两个字符之间插入间隔时返回对应的规则
+    let gap = |(prev_ch, prev_class): (char, CharClass), (cur_ch, cur_class): (char, CharClass), offset: usize| {
+        // both on the same side, or either is an emoji or ambiguous
+        if !(prev_class.is_cjk() && cur_class.is_western() || prev_class.is_western() && cur_class.is_cjk()) {
+            return None;
+        }
+
+        // special case: 关闭 Digits 规则时数字与全角字符之间不加空格
+        let is_digit_gap = !options.is_enabled(SpacingRule::Digits)
+            && (prev_class == CharClass::Digit || cur_class == CharClass::Digit);
+
+        // case 0: prev is space
+        if is_separator(prev_ch) || follows_spacer(offset) {
+            return None;
+        }
+
+        // case 1: prev is CJK, cur is western
+        if prev_class.is_cjk() && cur_class.is_western() {
+            // special case: 全角字符与半角标点之间不加空格, 全角标点与半角字符之间不加空格
+            // special case：货币符号后跟数字不加空格
+            let is_currency_before_number =
+                options.matches(SpacingRule::Currency, prev_ch) && cur_ch.is_numeric();
+
+            // special case: 谚文之后的西文不加空格，如品牌名 `삼성Galaxy`
+            let is_after_hangul =
+                options.is_enabled(SpacingRule::Hangul) && prev_class == CharClass::Hangul;
+
+            return (options.is_enabled(SpacingRule::FullHalf)
+                && !is_separator(cur_ch)
+                && !is_spacer_at(offset)
+                && !is_digit_gap
+                && !options.matches(SpacingRule::AfterFullPunctuation, prev_ch)
+                && !options.matches(SpacingRule::BeforeHalfPunctuation, cur_ch)
+                && !is_currency_before_number
+                && !is_after_hangul
+                && !is_protected(offset))
+            .then_some(SpacingRule::FullHalf);
+        }
+
+        // case 2: prev is western, cur is CJK
+        // special case: 半角符号与全角字符不加空格，半角字符与全角标点间不加空格
+        // special case: 货币符号后跟数字不加空格
+        let is_currency_before_number = options.matches(SpacingRule::Currency, prev_ch)
+            && !options.matches(SpacingRule::BeforeFullPunctuation, cur_ch);
+
+        // special case: 数字之后的量词、西文之后的助词和词尾不加空格，如 `3개`、`API를`
+        let is_attached_hangul = options.is_enabled(SpacingRule::Hangul)
+            && cur_class == CharClass::Hangul
+            && (prev_class == CharClass::Digit || hangul::is_attached(&text[offset..]));
+
+        // special case: 换行符不加空格
+        let is_line_break =
+            options.is_enabled(SpacingRule::LineStart) && prev_ch == '\n';
+
+        (options.is_enabled(SpacingRule::HalfFull)
+            && !is_separator(cur_ch)
+            && !is_spacer_at(offset)
+            && !options.matches(SpacingRule::AfterHalfSymbol, prev_ch)
+            && !options.matches(SpacingRule::BeforeFullPunctuation, cur_ch)
+            && !is_currency_before_number
+            && !is_digit_gap
+            && !is_attached_hangul
+            && !is_line_break
+            && !is_protected(offset))
+        .then_some(SpacingRule::HalfFull)
+    };
+
+    // 修改按偏移排序，`None` 表示插入或换成间隔
+    let mut edits: Vec<(Range<usize>, Option<char>)> = Vec::new();
+    let mut prev: Option<(char, CharClass)> = None;
+    // 被替换的空白和直接复制的 ASCII 字符都跳过
+    let mut skip_until = 0;
+    for (offset, cur_ch) in text.char_indices() {
+        if offset < skip_until {
+            continue;
+        }
+        if let Some(run) = runs.next_if(|run| run.start == offset) {
+            skip_until = run.end;
+            on_edit(run.clone(), spacer, SpacingRule::NormalizeSpaces);
+            edits.push((run, None));
+            prev = Some((' ', CharClass::Space));
+            continue;
+        }
+        let conversion = conversions.next_if(|(range, _, _)| range.start == offset);
+        let cur_ch = conversion.as_ref().map_or(cur_ch, |&(_, converted, _)| converted);
+
+        // Nothing goes between two ASCII characters, so the rest of an ASCII
+        // run is skipped up to the next character that may need a change
+        if conversion.is_none() && cur_ch.is_ascii() && prev.is_some_and(|(c, _)| c.is_ascii()) {
+            let mut end = text[offset..].find(|c: char| !c.is_ascii()).map_or(text.len(), |i| offset + i);
+            if let Some(run) = runs.peek() {
+                end = end.min(run.start);
             }
-            // the first char
-            (None, cur) => {
-                // println!("  First char case"); // Debug print
-                result.push(cur);
-                prev = Some(cur);
-                continue;
+            if let Some((range, _, _)) = conversions.peek() {
+                end = end.min(range.start);
             }
+            skip_until = end;
+            let last = text.as_bytes()[end - 1] as char;
+            prev = Some((last, CharClass::of(last)));
+            continue;
         }
-    }
 
-    // 将结果 Vec 转换为字符串
-    let final_result: String = result.into_iter().collect();
-    // println!("--- spacing returning: \"{}\" ---", final_result); // End marker
-    final_result
-}
+        let cur = (cur_ch, CharClass::of(cur_ch));
+        if let Some(rule) = prev.and_then(|prev| gap(prev, cur, offset)) {
+            on_edit(offset..offset, spacer, rule);
+            edits.push((offset..offset, None));
+        }
+        if let Some((range, converted, rule)) = conversion {
+            on_edit(range.clone(), converted.encode_utf8(&mut [0; 4]), rule);
+            edits.push((range, Some(converted)));
+        }
+        prev = Some(cur);
+    }
 
-#[derive(Debug, PartialEq)]
-pub enum CharWidth {
-    Half, // 半角字符
-    Full, // 全角字符
+    if edits.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len() + edits.len() * spacer.len());
+    let mut pos = 0;
+    for (range, converted) in edits {
+        result.push_str(&text[pos..range.start]);
+        match converted {
+            Some(c) => result.push(c),
+            None => result.push_str(spacer),
+        }
+        pos = range.end;
+    }
+    result.push_str(&text[pos..]);
+    Cow::Owned(result)
 }
 
-impl CharWidth {
-    pub fn from_char(c: char) -> Self {
-        match c.width() {
-            Some(1) | None => CharWidth::Half,
-            Some(2) => CharWidth::Full,
-            Some(_) => CharWidth::Full, // 其他宽度视为全角
+/// Sorts `ranges` and merges the overlapping ones, so that the result can be
+/// binary searched. Ranges that only touch stay apart, as the offset between
+/// them is inside neither.
+fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
         }
     }
+    merged
+}
 
-    pub fn is_half(&self) -> bool {
-        matches!(self, CharWidth::Half)
+/// `unspacing` 的实现，每去掉一个空格就以（空格的字节范围，空串，会插入它的规则）回调一次
+///
+/// 中日韩文字与西文之间的单个半角空格都是候选。空格两侧的词去掉这个空格后连在一起按 `spacing`
+/// 处理，`spacing` 在原处插入空格时去掉它。URL 等记号不含空白，所以只看这两个词就够了。
+pub(crate) fn unspacing_impl<'a>(
+    text: &'a str,
+    options: &SpacingOptions,
+    scope: Scope,
+    spacer: &str,
+    ignored: &[Range<usize>],
+    mut on_edit: impl FnMut(Range<usize>, &str, SpacingRule),
+) -> Cow<'a, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
     }
+    let mut result = String::new();
+    let mut pos = 0;
+    for (offset, _) in text.match_indices(' ') {
+        let (Some(prev), Some(next)) = (text[..offset].chars().next_back(), text[offset + 1..].chars().next())
+        else {
+            continue;
+        };
+        let (prev, next) = (CharClass::of(prev), CharClass::of(next));
+        if prev == CharClass::Space
+            || next == CharClass::Space
+            || !(prev.is_cjk() && next.is_western() || prev.is_western() && next.is_cjk())
+            || ignored.iter().any(|range| range.start <= offset && offset < range.end)
+        {
+            continue;
+        }
 
-    pub fn is_full(&self) -> bool {
-        matches!(self, CharWidth::Full)
+        let before = text[..offset].rsplit(char::is_whitespace).next().unwrap_or_default();
+        let after = text[offset + 1..].split(char::is_whitespace).next().unwrap_or_default();
+        let word = format!("{}{}", before, after);
+        let mut rule = None;
+        spacing_impl(&word, options, scope, spacer, &[], |range, _, r| {
+            if range.is_empty() && range.start == before.len() {
+                rule = Some(r);
+            }
+        });
+
+        if let Some(rule) = rule {
+            result.push_str(&text[pos..offset]);
+            pos = offset + 1;
+            on_edit(offset..offset + 1, "", rule);
+        }
+    }
+    if pos == 0 {
+        return Cow::Borrowed(text);
     }
+    result.push_str(&text[pos..]);
+    Cow::Owned(result)
+}
+
+/// 本身就起间隔作用的字符：半角空格、制表符，以及全角空格、不换行空格、日文排版中的四分空格（U+2005）等
+fn is_separator(c: char) -> bool {
+    whitespace::is_blank(c)
 }
 
 #[cfg(test)]
@@ -203,9 +483,9 @@ mod tests {
 
     #[test]
     fn test_newline_or_tab() {
-        assert_eq!(CharWidth::from_char('\n'), CharWidth::Half);
-        assert_eq!(CharWidth::from_char('\r'), CharWidth::Half);
-        assert_eq!(CharWidth::from_char('\t'), CharWidth::Half);
+        assert_eq!(CharClass::of('\n'), CharClass::Space);
+        assert_eq!(CharClass::of('\r'), CharClass::Space);
+        assert_eq!(CharClass::of('\t'), CharClass::Space);
 
         assert_eq!(spacing("a\nb"), "a\nb");
         assert_eq!(spacing("a\rb"), "a\rb");
@@ -217,18 +497,18 @@ mod tests {
     }
 
     #[test]
-    fn test_char_width() {
-        assert_eq!(CharWidth::from_char(' '), CharWidth::Half);
-        assert_eq!(CharWidth::from_char('a'), CharWidth::Half);
-        assert_eq!(CharWidth::from_char('A'), CharWidth::Half);
-        assert_eq!(CharWidth::from_char('1'), CharWidth::Half);
-        assert_eq!(CharWidth::from_char('，'), CharWidth::Full);
-        assert_eq!(CharWidth::from_char('。'), CharWidth::Full);
-        assert_eq!(CharWidth::from_char('！'), CharWidth::Full);
-        assert_eq!(CharWidth::from_char('？'), CharWidth::Full);
-        assert_eq!(CharWidth::from_char('￥'), CharWidth::Full);
-        assert_eq!(CharWidth::from_char('$'), CharWidth::Half);
-        assert_eq!(CharWidth::from_char('中'), CharWidth::Full);
+    fn test_char_class() {
+        assert_eq!(CharClass::of(' '), CharClass::Space);
+        assert_eq!(CharClass::of('a'), CharClass::Letter);
+        assert_eq!(CharClass::of('A'), CharClass::Letter);
+        assert_eq!(CharClass::of('1'), CharClass::Digit);
+        assert_eq!(CharClass::of('，'), CharClass::CjkPunctuation);
+        assert_eq!(CharClass::of('。'), CharClass::CjkPunctuation);
+        assert_eq!(CharClass::of('！'), CharClass::CjkPunctuation);
+        assert_eq!(CharClass::of('？'), CharClass::CjkPunctuation);
+        assert_eq!(CharClass::of('￥'), CharClass::CjkPunctuation);
+        assert_eq!(CharClass::of('$'), CharClass::Symbol);
+        assert_eq!(CharClass::of('中'), CharClass::Han);
     }
 
     #[test]
@@ -275,7 +555,7 @@ mod tests {
         // 测试全角和半角数字
         assert_eq!(
             spacing("中文数字１２３４５６７８９０和半角数字1234567890混排"),
-            "中文数字１２３４５６７８９０和半角数字 1234567890 混排"
+            "中文数字 １２３４５６７８９０ 和半角数字 1234567890 混排"
         );
 
         // 测试混合案例，包含函数名和引号
@@ -296,10 +576,10 @@ mod tests {
             "全角空格　和半角空格 混用"
         );
 
-        // 测试全角和半角字母数字混排
+        // 测试全角和半角字母数字混排，全角字母和数字也是西文
         assert_eq!(
             spacing("AＡBＢCＣ和abc以及1１２３和123混排"),
-            "A Ａ B Ｂ C Ｃ和 abc 以及 1 １２３和 123 混排"
+            "AＡBＢCＣ 和 abc 以及 1１２３ 和 123 混排"
         );
 
         // 测试路径表示
@@ -309,6 +589,261 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_scripts() {
+        assert_eq!(spacing("希腊字母α和西里尔字母Ж"), "希腊字母 α 和西里尔字母 Ж");
+        assert_eq!(spacing("かなKana언어"), "かな Kana 언어");
+        // 表情和宽度不定的标点两侧不加空格
+        assert_eq!(spacing("点赞👍🏻Like"), "点赞👍🏻Like");
+        assert_eq!(spacing("破折号—Dash…省略"), "破折号—Dash…省略");
+        assert_eq!(spacing("温度30°以上"), "温度 30°以上");
+    }
+
+    #[test]
+    fn test_japanese() {
+        let options = SpacingOptions::new().with_lang(Lang::Ja);
+        let spacing = |text: &str| spacing_with_options(text, &options).into_owned();
+
+        // 仮名とラテン文字
+        assert_eq!(spacing("これはRustで書かれたツールです"), "これは Rust で書かれたツールです");
+
+        // 漢字と数字は続けて書く
+        assert_eq!(spacing("第3章は2024年に追加"), "第3章は2024年に追加");
+
+        // 括弧
+        assert_eq!(spacing("「Hello」と『World』"), "「Hello」と『World』");
+
+        // 中点
+        assert_eq!(spacing("ジョン・Smithさん"), "ジョン・Smith さん");
+
+        // 長音記号
+        assert_eq!(spacing("サーバーAPIとユーザーID"), "サーバー API とユーザー ID");
+
+        // 波ダッシュ
+        assert_eq!(spacing("10〜20msかかる"), "10〜20ms かかる");
+
+        // 半角カナ
+        assert_eq!(spacing("ｶﾀｶﾅABCﾃﾞｰﾀ"), "ｶﾀｶﾅ ABC ﾃﾞｰﾀ");
+
+        // 半角句読点
+        assert_eq!(spacing("ﾃｽﾄ｡OK､ｱﾘｶﾞﾄｳ"), "ﾃｽﾄ｡OK､ｱﾘｶﾞﾄｳ");
+
+        // 句読点
+        assert_eq!(spacing("APIを使う。Gitも、Rustも"), "API を使う。Git も、Rust も");
+
+        // 四分アキはすでに間隔とみなす
+        assert_eq!(spacing("日本語\u{2005}English\u{2005}です"), "日本語\u{2005}English\u{2005}です");
+    }
+
+    #[test]
+    fn test_korean() {
+        // 默认规则集同样识别谚文
+        assert_eq!(spacing("API를 호출하고 Rust로 작성"), "API를 호출하고 Rust로 작성");
+        assert_eq!(spacing("삼성Galaxy와 LG그램"), "삼성Galaxy와 LG 그램");
+
+        let options = SpacingOptions::new().with_lang(Lang::Ko);
+        let spacing = |text: &str| spacing_with_options(text, &options).into_owned();
+
+        // 조사
+        assert_eq!(spacing("API를 호출하고 Git에서는 Rust로 작성"), "API를 호출하고 Git에서는 Rust로 작성");
+
+        // 접미사와 어미
+        assert_eq!(spacing("push했다가 API화된 기능"), "push했다가 API화된 기능");
+
+        // 브랜드 이름
+        assert_eq!(spacing("삼성Galaxy와 카카오T"), "삼성Galaxy와 카카오T");
+
+        // 숫자와 단위
+        assert_eq!(spacing("3개의 파일을 10분 만에, 第3章"), "3개의 파일을 10분 만에, 第3章");
+
+        // 조사가 아닌 낱말
+        assert_eq!(spacing("Rust프로그래밍과 Python이름"), "Rust 프로그래밍과 Python 이름");
+
+        // 괄호와 기호 뒤의 조사
+        assert_eq!(spacing("(API)를 100%로"), "(API)를 100%로");
+
+        // 한자와 라틴 문자
+        assert_eq!(spacing("漢字Hanja"), "漢字 Hanja");
+    }
+
+    #[test]
+    fn test_full_width_punctuation() {
+        // 默认关闭
+        assert_eq!(spacing("你好,世界"), "你好, 世界");
+
+        let options = SpacingOptions::new().enable(SpacingRule::FullWidthPunctuation);
+        let spacing = |text: &str| spacing_with_options(text, &options).into_owned();
+        assert_eq!(
+            spacing("使用了Python的print()函数打印\"你好,世界\""),
+            "使用了 Python 的 print() 函数打印\"你好，世界\""
+        );
+        assert_eq!(spacing("真的吗?是的!注意:参数(可选)"), "真的吗？是的！注意：参数（可选）");
+        assert_eq!(spacing("第一句.第二句."), "第一句。第二句.");
+        assert_eq!(spacing("圆周率是3.14,约等于22/7..."), "圆周率是 3.14, 约等于 22/7...");
+        assert_eq!(spacing("访问https://例子.com/路径,了解"), "访问 https://例子.com/路径,了解");
+        assert_eq!(spacing("可选链a?.b和不等!="), "可选链 a?.b 和不等!=");
+        assert_eq!(
+            process_js_with_options("let s = '你好,世界'; // 注释:说明", &options).unwrap(),
+            "let s = '你好，世界'; // 注释：说明"
+        );
+    }
+
+    #[test]
+    fn test_conversions_skip_protected_tokens() {
+        let options = SpacingOptions::new()
+            .enable(SpacingRule::FullWidthPunctuation)
+            .enable(SpacingRule::HalfWidthAlphanumerics);
+        for token in [
+            "user@公司.中国",
+            "https://例子.com/路径,参数(可选)",
+            "~/配置.文件/备份(旧)",
+            r"C:\用户\文档:备份",
+            "#话题#",
+            "@张三",
+        ] {
+            for text in [format!("邮件{token}"), format!("见{token} 吧")] {
+                let result = spacing_with_options(&text, &options);
+                assert!(result.contains(token), "{} -> {}", text, result);
+            }
+        }
+        assert_eq!(spacing("邮件user@公司.中国"), "邮件 user@公司.中国");
+    }
+
+    #[test]
+    fn test_half_width_alphanumerics() {
+        // 默认关闭
+        assert_eq!(spacing("数字１２３和"), "数字 １２３ 和");
+
+        let options = SpacingOptions::new().enable(SpacingRule::HalfWidthAlphanumerics);
+        let spacing = |text: &str| spacing_with_options(text, &options).into_owned();
+        assert_eq!(spacing("数字１２３和"), "数字 123 和");
+        assert_eq!(
+            spacing("中文数字１２３４５６７８９０和半角数字1234567890混排"),
+            "中文数字 1234567890 和半角数字 1234567890 混排"
+        );
+        assert_eq!(spacing("ＡＢＣ公司占比５０％，电话＠我"), "ABC 公司占比 50%，电话 @我");
+        // 全角标点和可能改变代码含义的符号保持不变
+        assert_eq!(spacing("（注）＂引号＂＼＄"), "（注）＂引号＂＼＄");
+        assert_eq!(
+            process_json_with_options("{\"ＫＥＹ\": \"值ＶＡＬＵＥ\"}", &options).unwrap(),
+            "{\"ＫＥＹ\": \"值 VALUE\"}"
+        );
+        assert_eq!(
+            process_js_with_options("let ｘ = '版本２'; // 注释ＡＢ", &options).unwrap(),
+            "let ｘ = '版本 2'; // 注释 AB"
+        );
+    }
+
+    #[test]
+    fn test_unspacing() {
+        assert_eq!(unspacing("当你凝视着 bug，bug 也凝视着你"), "当你凝视着bug，bug也凝视着你");
+        assert_eq!(unspacing("中文 English 混排，123 个"), "中文English混排，123个");
+        // 其他空白，以及 spacing 不会插入空格的位置保持不变
+        assert_eq!(unspacing("中文  English\t混排\nabc 中\u{3000}a"), "中文  English\t混排\nabc中\u{3000}a");
+        assert_eq!(unspacing("价格 $五十，@ 用户，hello world"), "价格$五十，@ 用户，hello world");
+        assert_eq!(unspacing("访问 https://例子.com 吧"), "访问https://例子.com 吧");
+        // 按规则配置判断
+        let options = SpacingOptions::new().with_lang(Lang::Ja);
+        assert_eq!(unspacing_with_options("学习 C 语言，第 3 章", &options), "学习C语言，第 3 章");
+
+        let options = SpacingOptions::new().with_unspacing(true);
+        assert_eq!(spacing_with_options("中文 English", &options), "中文English");
+        assert_eq!(
+            process_js_with_options("let s = \"字符串 String\"; // 注释 Comment", &options).unwrap(),
+            "let s = \"字符串String\"; // 注释Comment"
+        );
+        assert_eq!(
+            process_markdown_with_options("# 标题 Title\n\n段落 Text", &options).unwrap(),
+            "# 标题Title\n\n段落Text"
+        );
+    }
+
+    proptest::proptest! {
+        // Removing a space may join a token that keeps it: the space in `/ 文/a`
+        // stays as `/文/a` is a path, but spacing it first gives `/ 文 /a`, where
+        // both spaces go. So the round trip holds for the character rules only
+        #[test]
+        fn test_unspacing_round_trip(text in "[中文かな한글。，「a-zA-Z0-9 .,:$@#/~_()\"\n]{0,24}") {
+            let options = SpacingOptions::new().disable(SpacingRule::ProtectedTokens);
+            let unspaced = unspacing_with_options(&text, &options);
+            let spaced = spacing_with_options(&text, &options);
+            proptest::prop_assert_eq!(unspacing_with_options(&spaced, &options), unspaced);
+        }
+    }
+
+    #[test]
+    fn test_normalize_spaces() {
+        // 默认关闭，已有的各种空白都视为间隔
+        let text = "中文  English\t中文　English\u{a0}中文 English　中文";
+        assert_eq!(spacing(text), text);
+
+        let options = SpacingOptions::new().enable(SpacingRule::NormalizeSpaces);
+        let spacing = |text: &str| spacing_with_options(text, &options).into_owned();
+        assert_eq!(spacing(text), "中文 English 中文 English 中文 English 中文");
+        // 缩进、Markdown 硬换行和同一种文字之间的空白保持不变
+        assert_eq!(spacing("  中文  English  \n\t中文　　中文"), "  中文 English  \n\t中文　　中文");
+        assert_eq!(
+            process_markdown_with_options("段落  Text  \n下一行\u{3000}Line\n\n    代码  Code", &options).unwrap(),
+            "段落 Text  \n下一行 Line\n\n    代码  Code"
+        );
+        // 注释中连续的空白视为对齐
+        assert_eq!(
+            process_js_with_options("let s = '字符串  String'; // 名称    Name\t说明　Text", &options).unwrap(),
+            "let s = '字符串 String'; // 名称    Name\t说明 Text"
+        );
+    }
+
+    #[test]
+    fn test_borrowed() {
+        // 无需修改时返回原文，不复制
+        for text in ["", "plain ASCII text", "中文 English", "纯中文"] {
+            assert!(matches!(spacing(text), Cow::Borrowed(_)), "{}", text);
+        }
+        assert!(matches!(unspacing("中文English"), Cow::Borrowed(_)));
+        assert!(matches!(spacing("中文English"), Cow::Owned(_)));
+        // 中日韩文字之间的 ASCII 片段
+        assert_eq!(spacing("中a b c中d, e中"), "中 a b c 中 d, e 中");
+        assert_eq!(spacing("x中\n\nabc\n中x"), "x 中\n\nabc\n中 x");
+    }
+
+    #[test]
+    fn test_spacing_with_context() {
+        assert_eq!(spacing_with_context(Some('中'), "English文字", Some('x')), " English 文字 ");
+        assert_eq!(spacing_with_context(Some(' '), "English", None), "English");
+        assert_eq!(spacing_with_context(None, "", Some('E')), "");
+        // Rules that look at the context, e.g. line starts, see through it
+        assert_eq!(spacing_with_context(Some('，'), "English", None), "English");
+
+        let options = SpacingOptions::default().with_unspacing(true);
+        assert_eq!(spacing_with_context_and_options(Some('中'), " English ", Some('文'), &options), "English");
+        let options = SpacingOptions::new().with_spacer(Spacer::Thin);
+        assert_eq!(spacing_with_context_and_options(Some('中'), "English", None, &options), "\u{2009}English");
+    }
+
+    #[test]
+    fn test_spacer() {
+        let options = SpacingOptions::new().with_spacer(Spacer::Thin);
+        let spacing = |text: &str| spacing_with_options(text, &options).into_owned();
+        assert_eq!(spacing("中文English混排"), "中文\u{2009}English\u{2009}混排");
+        // 已有的间隔不再重复插入
+        assert_eq!(spacing("中文\u{2009}English 混排"), "中文\u{2009}English 混排");
+        assert_eq!(
+            process_html_with_options("<title>标题Title</title><p title=\"提示Tip\">中文English</p>", &options).unwrap(),
+            "<title>标题&thinsp;Title</title><p title=\"提示&thinsp;Tip\">中文&thinsp;English</p>"
+        );
+
+        let options = SpacingOptions::new().with_spacer(Spacer::NoBreak).enable(SpacingRule::NormalizeSpaces);
+        assert_eq!(spacing_with_options("中文 English\t混排", &options), "中文\u{a0}English\u{a0}混排");
+
+        // 自定义的标签只写入能包含标签的位置
+        let gap = "<span class=\"gap\"></span>";
+        let options = SpacingOptions::new().with_spacer(Spacer::Custom(gap.to_string()));
+        let html = "<title>标题Title</title><p title=\"提示Tip\">中文English</p>";
+        let expected = format!("<title>标题 Title</title><p title=\"提示 Tip\">中文{}English</p>", gap);
+        assert_eq!(process_html_with_options(html, &options).unwrap(), expected);
+        assert_eq!(process_html_with_options(&expected, &options).unwrap(), expected);
+    }
+
     #[test]
     fn test_symbols() {
         // 波浪号测试
@@ -366,6 +901,164 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_spacing_with_options() {
+        let options = SpacingOptions::new().disable(SpacingRule::HalfFull);
+        assert_eq!(spacing_with_options("中文English中文", &options), "中文 English中文");
+
+        let options = SpacingOptions::new().disable(SpacingRule::AfterFullPunctuation);
+        assert_eq!(spacing_with_options("你好，bug", &options), "你好， bug");
+
+        let options = SpacingOptions::new().with_chars(SpacingRule::AfterHalfSymbol, "");
+        assert_eq!(spacing_with_options("&用户", &options), "& 用户");
+
+        let options = SpacingOptions::new().disable(SpacingRule::ProtectedTokens);
+        assert_eq!(spacing_with_options("见/home/用户abc/文件", &options), "见 /home/ 用户 abc/ 文件");
+        assert_eq!(spacing("见/home/用户abc/文件"), "见 /home/用户abc/文件");
+
+        let options = SpacingOptions::new().disable(SpacingRule::FullHalf);
+        assert_eq!(
+            process_js_with_options("// 注释comment中文", &options).unwrap(),
+            "// 注释comment 中文"
+        );
+
+        let options = SpacingOptions::new().add_exceptions(["Wi-Fi网络", "iPhone15"]);
+        assert_eq!(
+            spacing_with_options("连接Wi-Fi网络的iPhone15手机", &options),
+            "连接 Wi-Fi网络的 iPhone15 手机"
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        let options = SpacingOptions::new().with_scopes([Scope::DocComments]);
+        assert_eq!(
+            process_rust_with_options("/// 文档Doc\nlet s = \"字符串String\"; // 注释Comment", &options)
+                .unwrap(),
+            "/// 文档 Doc\nlet s = \"字符串String\"; // 注释Comment"
+        );
+        assert_eq!(
+            process_js_with_options("/** 文档Doc */ /* 注释Comment */", &options).unwrap(),
+            "/** 文档 Doc */ /* 注释Comment */"
+        );
+
+        let options = SpacingOptions::new().with_scopes([Scope::Comments]);
+        assert_eq!(
+            process_json5_with_options("{ a: '甲b', // 注释c\n}", &options).unwrap(),
+            "{ a: '甲b', // 注释 c\n}"
+        );
+        assert_eq!(
+            process_html_with_options("<p>段落Text</p><!-- 注释Comment -->", &options).unwrap(),
+            "<p>段落Text</p><!-- 注释 Comment -->"
+        );
+        assert_eq!(
+            process_php_with_options("<?php echo '单引号String'; // 注释Comment ?>", &options)
+                .unwrap(),
+            "<?php echo '单引号String'; // 注释 Comment ?>"
+        );
+
+        let options = SpacingOptions::new().with_scopes([Scope::Text, Scope::Strings]);
+        assert_eq!(
+            process_rust_with_options("/// 文档Doc\n// 注释Comment", &options).unwrap(),
+            "/// 文档Doc\n// 注释Comment"
+        );
+
+        // Comments but not strings, in every code format
+        let options = SpacingOptions::new().with_scopes([Scope::Comments, Scope::DocComments]);
+        assert_eq!(
+            process_js_with_options("let s = `模板Template`; /* 注释Comment */", &options).unwrap(),
+            "let s = `模板Template`; /* 注释 Comment */"
+        );
+        assert_eq!(
+            process_rust_with_options("let s = r\"原始Raw\"; // 注释Comment", &options).unwrap(),
+            "let s = r\"原始Raw\"; // 注释 Comment"
+        );
+        assert_eq!(
+            process_css_with_options("a { content: '内容Content'; } /* 注释Comment */", &options).unwrap(),
+            "a { content: '内容Content'; } /* 注释 Comment */"
+        );
+        assert_eq!(
+            process_php_with_options("<?php $s = <<<EOT\n文档Heredoc\nEOT; # 注释Comment\n", &options).unwrap(),
+            "<?php $s = <<<EOT\n文档Heredoc\nEOT; # 注释 Comment\n"
+        );
+        assert_eq!(
+            process_html_with_options(
+                "<p>段落Text</p><script>let s = '字符串String'; // 注释Comment\n</script><style>a { content: '内容Content'; } /* 注释Comment */</style>",
+                &options
+            )
+            .unwrap(),
+            "<p>段落Text</p><script>let s = '字符串String'; // 注释 Comment\n</script><style>a { content: '内容Content'; } /* 注释 Comment */</style>"
+        );
+
+        // Strings only, including the ones in embedded scripts
+        let options = SpacingOptions::new().with_scopes([Scope::Strings]);
+        assert_eq!(
+            process_html_with_options("<p>段落Text</p><script>let s = '字符串String'; // 注释Comment\n</script>", &options)
+                .unwrap(),
+            "<p>段落Text</p><script>let s = '字符串 String'; // 注释Comment\n</script>"
+        );
+        assert_eq!(
+            process_json_with_options(r#"{"键Key": "值Value"}"#, &SpacingOptions::new().with_scopes([Scope::Comments])).unwrap(),
+            r#"{"键Key": "值Value"}"#
+        );
+    }
+
+    #[test]
+    fn test_protected_tokens() {
+        assert_eq!(spacing("打开https://例子.com/路径?q=中文"), "打开 https://例子.com/路径?q=中文");
+        assert_eq!(spacing("发邮件给user@公司.cn吧"), "发邮件给 user@公司.cn 吧");
+        assert_eq!(spacing(r"保存到C:\用户\文档abc里"), r"保存到 C:\用户\文档abc里");
+        assert_eq!(spacing("和~/配置/文件"), "和 ~/配置/文件");
+        assert_eq!(spacing("见/home/用户/文档"), "见 /home/用户/文档");
+        assert_eq!(spacing("文件在/usr/本地/库目录"), "文件在 /usr/本地/库目录");
+        assert_eq!(spacing("打开~/Documents目录"), "打开 ~/Documents 目录");
+        assert_eq!(spacing("升级到v1.2.3版本"), "升级到 v1.2.3 版本");
+        assert_eq!(spacing("感谢@张三abc 和#话题Topic#的支持"), "感谢 @张三abc 和 #话题Topic#的支持");
+    }
+
+    #[test]
+    fn test_directives() {
+        assert_eq!(
+            process_js("let a = '甲a'; // paranoid-space-ignore\n// paranoid-space-ignore-next-line\nlet b = '乙b';\nlet c = '丙c';")
+                .unwrap(),
+            "let a = '甲a'; // paranoid-space-ignore\n// paranoid-space-ignore-next-line\nlet b = '乙b';\nlet c = '丙 c';"
+        );
+        assert_eq!(
+            process_rust("// paranoid-space off\nlet s = \"甲a\";\n/// 文档Doc\n// paranoid-space on\nlet t = \"乙b\";")
+                .unwrap(),
+            "// paranoid-space off\nlet s = \"甲a\";\n/// 文档Doc\n// paranoid-space on\nlet t = \"乙 b\";"
+        );
+        assert_eq!(
+            process_css("/* paranoid-space-ignore-next-line */\na { content: \"甲a\"; }\nb { content: \"乙b\"; }")
+                .unwrap(),
+            "/* paranoid-space-ignore-next-line */\na { content: \"甲a\"; }\nb { content: \"乙 b\"; }"
+        );
+        assert_eq!(
+            process_json5("{\n  // paranoid-space-ignore-next-line\n  a: '甲a',\n  b: '乙b',\n}").unwrap(),
+            "{\n  // paranoid-space-ignore-next-line\n  a: '甲a',\n  b: '乙 b',\n}"
+        );
+        assert_eq!(
+            process_html("<p>甲a</p>\n<!-- paranoid-space off -->\n<p title=\"乙b\">丙c</p>\n<!-- paranoid-space on -->\n<p>丁d</p>")
+                .unwrap(),
+            "<p>甲 a</p>\n<!-- paranoid-space off -->\n<p title=\"乙b\">丙c</p>\n<!-- paranoid-space on -->\n<p>丁 d</p>"
+        );
+        assert_eq!(
+            process_php("<?php\n# paranoid-space-ignore-next-line\necho '甲a';\necho '乙b'; // 注释Comment paranoid-space-ignore\n?>")
+                .unwrap(),
+            "<?php\n# paranoid-space-ignore-next-line\necho '甲a';\necho '乙 b'; // 注释 Comment paranoid-space-ignore\n?>"
+        );
+        assert_eq!(
+            process_markdown("甲a\n\n<!-- paranoid-space off -->\n\n乙b\n\n<!-- paranoid-space on -->\n\n丙c `<!-- paranoid-space off -->` 丁d")
+                .unwrap(),
+            "甲 a\n\n<!-- paranoid-space off -->\n\n乙b\n\n<!-- paranoid-space on -->\n\n丙 c `<!-- paranoid-space off -->` 丁 d"
+        );
+        // Directives only count in comments
+        assert_eq!(
+            process_js("let a = '// paranoid-space off';\nlet b = '乙b';").unwrap(),
+            "let a = '// paranoid-space off';\nlet b = '乙 b';"
+        );
+    }
+
     #[test]
     fn test_process_escape_sequences() {
         let input = r#"\t"#;
@@ -395,16 +1088,16 @@ mod tests {
     }
 
     #[test]
-    fn test_charwidth_enum_mapping() {
+    fn test_char_class_mapping() {
         // Test specific characters involved in the failing case
-        assert_eq!(CharWidth::Full, CharWidth::from_char('好'), "Mapping for '好'");
-        assert_eq!(CharWidth::Half, CharWidth::from_char('w'), "Mapping for 'w'");
+        assert_eq!(CharClass::Han, CharClass::of('好'), "Mapping for '好'");
+        assert_eq!(CharClass::Letter, CharClass::of('w'), "Mapping for 'w'");
 
         // Test some other common cases
-        assert_eq!(CharWidth::Full, CharWidth::from_char('，'), "Mapping for full-width comma");
-        assert_eq!(CharWidth::Half, CharWidth::from_char(','), "Mapping for half-width comma");
-        assert_eq!(CharWidth::Half, CharWidth::from_char(' '), "Mapping for space");
-        assert_eq!(CharWidth::Half, CharWidth::from_char('\n'), "Mapping for newline"); // Maps None to Half
+        assert_eq!(CharClass::CjkPunctuation, CharClass::of('，'), "Mapping for full-width comma");
+        assert_eq!(CharClass::Symbol, CharClass::of(','), "Mapping for half-width comma");
+        assert_eq!(CharClass::Space, CharClass::of(' '), "Mapping for space");
+        assert_eq!(CharClass::Space, CharClass::of('\n'), "Mapping for newline");
     }
 
     #[test]
@@ -1,136 +1,127 @@
-extern crate pest;
+use anyhow::Result;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
 
-use pest::Parser;
-use anyhow::{Result};
-use crate::spacing; // Import the spacing function
-
-#[derive(Parser)]
-#[grammar = "grammar/markdown.pest"]
-struct MarkdownParser;
+use crate::edit::Context;
+use crate::segment::{self, CommentStyle, Segment, SegmentKind, Segments};
+use crate::{Element, SpacingOptions, html};
 
 // Main processing function
 pub fn process(text: &str) -> Result<String> {
-    match MarkdownParser::parse(Rule::document, text) {
-        Ok(pairs) => {
-            // Process the parsed document, pair by pair
-            Ok(pairs.map(process_pair).collect::<String>())
-        }
-        Err(e) => {
-            // On parsing error, log it and return the original text
-            eprintln!("Markdown Parse failed: {:?}", e);
-            Err(e.into())
-        }
-    }
+    process_with_options(text, &SpacingOptions::default())
 }
 
-// Recursive function to process parsed pairs, reconstructing Markdown
-fn process_pair(pair: pest::iterators::Pair<Rule>) -> String {
-    match pair.as_rule() {
-        // Structural Rules: Recursively process inner content
-        Rule::document | Rule::paragraph | Rule::link_text | Rule::image_alt => {
-            pair.into_inner().map(process_pair).collect()
-        }
+pub fn process_with_options(text: &str, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(text, &segment(text)?))
+}
+
+/// Spaces `text` as Markdown, without directives; used for doc comments.
+pub(crate) fn process_with_context(text: &str, ctx: &mut Context) -> String {
+    segment::apply(text, &segments(text), ctx)
+}
 
-        // Basic Content Rules:
-        Rule::text => spacing(pair.as_str()), // Apply spacing to raw text segments
-        Rule::WHITESPACE => pair.as_str().to_string(), // Preserve whitespace
-        Rule::link_url | Rule::image_url | Rule::language => pair.as_str().to_string(),
-        Rule::NEWLINE => "\n".to_string(),
-        Rule::EOI => "".to_string(),
-
-        // --- Block Elements Reconstruction ---
-        Rule::heading => {
-            let mut inner = pair.into_inner();
-            let marker = inner.next().unwrap().as_str(); // # marker
-            let content = inner.map(process_pair).collect::<String>();
-            format!("{}{}\n", marker, content) // Ensure newline after heading
+/// CommonMark accepts any input, so this never fails.
+pub(crate) fn segment(text: &str) -> Result<Vec<Segment>> {
+    Ok(segments(text))
+}
+
+/// Prose and HTML comments, with everything else as code: the output differs
+/// from the input only by inserted spaces.
+///
+/// Prose is paragraphs, headings, list items, table cells, link text and image
+/// alt text. Code, HTML blocks and front matter are left out. Emphasis, links
+/// and code spans are marked as inline tags, so that the text around them is
+/// spaced as one run.
+fn segments(text: &str) -> Vec<Segment> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_GFM
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+
+    let mut segments = Segments::new();
+    // The end of the last segment; pulldown-cmark may report text again
+    let mut end = 0;
+    // Nesting depth of blocks whose text must not be touched
+    let mut verbatim = 0;
+    // The start of an opening marker such as `**`, which ends where its content begins
+    let mut open: Option<usize> = None;
+    let inline = |open| SegmentKind::Tag { element: Element::Inline, open };
+    for (event, range) in Parser::new_ext(text, options).into_offset_iter() {
+        if let (Some(start), Event::Text(_) | Event::InlineHtml(_) | Event::Code(_)) = (open, &event)
+            && range.start >= end
+        {
+            segments.push(inline(true), start..range.start);
+            open = None;
         }
-        Rule::code_block => {
-            let mut inner = pair.into_inner();
-            let lang_node = inner.next().unwrap();
-            let lang = if lang_node.as_rule() == Rule::language { 
-                lang_node.as_str() 
-            } else {
-                "" // No language specified
-            };
-            // The rest should be code_content or NEWLINEs, get the raw string
-            let code = inner.map(|p| p.as_str()).collect::<String>(); 
-            if lang.is_empty() {
-                 format!("```\n{}\n```\n", code.trim_end())
-            } else {
-                 format!("```{}\n{}\n```\n", lang, code.trim_end()) // Corrected escape and newline
+        match event {
+            Event::Html(_) | Event::InlineHtml(_) | Event::Text(_) if range.start < end => {}
+            Event::Html(html) | Event::InlineHtml(html) if html.trim_start().starts_with("<!--") => {
+                let start = range.start + text[range.clone()].find("<!--").unwrap_or(0) + 4;
+                // A comment over several lines comes as one event per line: it
+                // is one segment up to `-->`, and the events it covers are
+                // skipped; without `-->` only the first line is taken
+                let comment_end = text[start..].find("-->").map_or(range.end, |i| start + i);
+                segments.push(SegmentKind::Comment { style: CommentStyle::Html }, start..comment_end);
+                end = comment_end;
             }
-        }
-        Rule::blockquote => {
-             let mut inner = pair.into_inner();
-             let marker = inner.next().unwrap().as_str(); // > marker
-             let content = inner.map(process_pair).collect::<String>();
-             format!("{}{}\n", marker, content) // Ensure newline
-        }
-        Rule::list_item => {
-             let mut inner = pair.into_inner();
-             let marker = inner.next().unwrap().as_str(); // list marker (*, -, 1., etc.)
-             let content = inner.map(process_pair).collect::<String>();
-             format!("{}{}\n", marker, content)
-        }
-         Rule::task_item => {
-            let mut inner = pair.into_inner();
-            let marker = inner.next().map(|p| p.as_str()).unwrap_or("[ ]"); // Safer unwrap
-            let content = inner.map(process_pair).collect::<String>();
-            format!("- {} {}\n", marker, content)
-        }
-        Rule::horizontal_rule => format!("{}\n", pair.as_str()),
-
-        // --- Inline Elements Reconstruction ---
-        Rule::inline_code => {
-            let code_content = pair.as_str()
-                                .trim_start_matches('`')
-                                .trim_end_matches('`');
-            format!("`{}`", code_content)
-        },
-        Rule::strong => format!("**{}**", pair.into_inner().map(process_pair).collect::<String>()),
-        Rule::emphasis => format!("*{}*", pair.into_inner().map(process_pair).collect::<String>()), // Using '*' for simplicity
-        Rule::link => {
-            let pair_str = pair.as_str(); // Get string representation first
-            let mut inner = pair.into_inner();
-            if let (Some(text_pair), Some(url_pair)) = (inner.next(), inner.next()) {
-                let text = spacing(text_pair.as_str()); // Apply spacing to link text
-                let url = url_pair.as_str();
-                format!("[{}]({})", text, url)
-            } else {
-                // Grammar matched link, but inner structure is wrong? Return raw string.
-                eprintln!("Warning: Malformed link inner structure: {}", pair_str);
-                pair_str.to_string()
+            Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::MetadataBlock(_)) => {
+                verbatim += 1
             }
-        }
-        Rule::image => {
-            let pair_str = pair.as_str(); // Get string representation first
-            let mut inner = pair.into_inner();
-            if let (Some(alt_text_pair), Some(url_pair)) = (inner.next(), inner.next()) {
-                let alt_text = spacing(alt_text_pair.as_str()); // Apply spacing to alt text
-                let url = url_pair.as_str();
-                format!("![{}]({})", alt_text, url)
-            } else {
-                // Grammar matched image, but inner structure is wrong? Return raw string.
-                eprintln!("Warning: Malformed image inner structure: {}", pair_str);
-                pair_str.to_string()
+            Event::End(TagEnd::CodeBlock | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_)) => {
+                verbatim -= 1
             }
-        }
-
-        // Fallback for unhandled rules (should ideally be exhaustive)
-        // Print a warning and the raw text for debugging
-        _ => {
-             eprintln!("Warning: Unhandled rule {:?} in process_pair: {}", pair.as_rule(), pair.as_str());
-             pair.as_str().to_string() // Return raw string as fallback
+            // pulldown-cmark splits text at escapes and special characters,
+            // and the pieces are joined so that spacing sees the whole run
+            Event::Text(_) if verbatim == 0 => {
+                segments.push(SegmentKind::Text, range.clone());
+                end = range.end;
+            }
+            // Tags are kept but for their displayed attributes; what only looks
+            // like a tag, such as `Vec<T>` in doc comments, stays part of the
+            // surrounding sentence, so `Vec<T>类型` is still spaced
+            Event::InlineHtml(_) if verbatim == 0 => {
+                match html::inline_tag(&text[range.clone()]) {
+                    Some(tag) => segments.extend(range.start, tag, None),
+                    None => segments.push(SegmentKind::Text, range.clone()),
+                }
+                end = range.end;
+            }
+            Event::Start(Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link { .. }) if verbatim == 0 => {
+                open.get_or_insert(range.start);
+            }
+            Event::End(TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Link) if verbatim == 0 => {
+                match open.take() {
+                    // Markers around nothing stay code
+                    Some(_) => {}
+                    None if range.end > segments.end() => {
+                        segments.push(inline(false), segments.end()..range.end);
+                        end = range.end;
+                    }
+                    None => {}
+                }
+            }
+            Event::Code(_) if verbatim == 0 => {
+                let source = &text[range.clone()];
+                let ticks = source.len() - source.trim_start_matches('`').len();
+                let code = |open| SegmentKind::Tag { element: Element::Code, open };
+                if source.len() > 2 * ticks {
+                    segments.push(code(true), range.start..range.start + ticks);
+                    segments.push(SegmentKind::InlineCode, range.start + ticks..range.end - ticks);
+                    segments.push(code(false), range.end - ticks..range.end);
+                }
+                end = range.end;
+            }
+            _ => {}
         }
     }
+    segments.finish(text.len())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    // Existing tests will be restored here later
     #[test]
     fn test_new_line(){
         let result = process("\nThis is a block doc comment\nThis is another line\n");
@@ -230,4 +221,118 @@ fn main() {
         let actual = process(input).unwrap();
         assert_eq!(actual, expected); // Use the stored 'actual'
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_gfm() {
+        let input = r#"---
+title: 标题Title
+---
+
+1. 列表List
+   - [x] 任务Task
+   - [ ] _强调Emphasis_ 与 **粗体*嵌套Nested***
+
+| 表头Header | 值 |
+|:--|--:|
+| 单元格Cell | `代码Code` |
+
+脚注Footnote[^1]
+
+[^1]: 注释Note
+
+<div>
+HTML块Block
+</div>
+
+    缩进代码Indented
+
+~~删除Deleted~~ \[转义Escaped\]
+"#;
+        let expected = r#"---
+title: 标题Title
+---
+
+1. 列表 List
+   - [x] 任务 Task
+   - [ ] _强调 Emphasis_ 与 **粗体*嵌套 Nested***
+
+| 表头 Header | 值 |
+|:--|--:|
+| 单元格 Cell | `代码Code` |
+
+脚注 Footnote[^1]
+
+[^1]: 注释 Note
+
+<div>
+HTML块Block
+</div>
+
+    缩进代码Indented
+
+~~删除 Deleted~~ \[转义 Escaped\]
+"#;
+        assert_eq!(process(input).unwrap(), expected);
+        assert_eq!(process("泛型Vec<T>类型").unwrap(), "泛型 Vec<T> 类型");
+    }
+
+    #[test]
+    fn test_inline_html() {
+        // Only displayed attributes of inline tags change
+        assert_eq!(
+            process("段落<b class=\"类名abc\" title=\"提示Tip\">粗体</b>文字").unwrap(),
+            "段落<b class=\"类名abc\" title=\"提示 Tip\">粗体</b>文字"
+        );
+        assert_eq!(process("<?处理abc?>和<!DOCTYPE 文档abc>").unwrap(), "<?处理abc?>和<!DOCTYPE 文档abc>");
+        assert_eq!(process("泛型Vec<T>类型和Box<dyn Fn>").unwrap(), "泛型 Vec<T> 类型和 Box<dyn Fn>");
+    }
+
+    #[test]
+    fn test_html_comments() {
+        assert_eq!(process("<!-- 注释Comment -->
+
+段落Text").unwrap(), "<!-- 注释 Comment -->
+
+段落 Text");
+        // Comments after other markup stay as they are
+        assert_eq!(
+            process("<div>块Block<!-- 注释Comment --></div>").unwrap(),
+            "<div>块Block<!-- 注释Comment --></div>"
+        );
+        assert_eq!(process("<!-- 多行\n注释Comment -->").unwrap(), "<!-- 多行\n注释 Comment -->");
+        assert_eq!(process("<!--\n注释Comment\n-->\n\n段落Text").unwrap(), "<!--\n注释 Comment\n-->\n\n段落 Text");
+        assert_eq!(process("段落<!-- 行内\n注释Comment -->文字").unwrap(), "段落<!-- 行内\n注释 Comment -->文字");
+    }
+
+    #[test]
+    fn test_inline_boundaries() {
+        let cases = [
+            ("中文**bold**English", "中文 **bold**English"),
+            ("**粗体**English", "**粗体** English"),
+            ("中文`code`文字", "中文 `code` 文字"),
+            ("中文[Link](http://example.com)文字", "中文 [Link](http://example.com) 文字"),
+            ("***中文***English", "***中文*** English"),
+            ("中文<https://example.com>文字", "中文 <https://example.com> 文字"),
+            ("中文**粗体**文字", "中文**粗体**文字"),
+            ("中文<b>English</b>", "中文 <b>English</b>"),
+            ("中文<br>English", "中文<br>English"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(process(input).unwrap(), expected);
+        }
+
+        let options = SpacingOptions::new().with_separators([Element::Code]);
+        assert_eq!(process_with_options("中文`code`文字", &options).unwrap(), "中文`code`文字");
+        assert_eq!(process_with_options("中文<br>English", &options).unwrap(), "中文 <br>English");
+        let options = SpacingOptions::new().with_separators([Element::Inline]);
+        assert_eq!(process_with_options("中文<b>English</b>", &options).unwrap(), "中文<b>English</b>");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Nothing but the inserted spaces changes, even for input in odd forms
+        for input in ["_x_ *y*", "-  item\n+ other\n", "```\n  code  \n```", "- [X]   task", "  \n\n"] {
+            assert_eq!(process(input).unwrap(), input);
+        }
+    }
+}
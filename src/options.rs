@@ -0,0 +1,664 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+
+/// 可单独开关的空格规则
+///
+/// 每条规则都有一个稳定的 ID（见 [`SpacingRule::id`]），可用于配置、命令行和诊断输出。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpacingRule {
+    /// 全角字符后接半角字符时插入空格
+    FullHalf,
+    /// 半角字符后接全角字符时插入空格
+    HalfFull,
+    /// 数字与全角字符之间也插入空格，关闭后如 `第3章`、`3つ` 保持相连
+    Digits,
+    /// 谚文按韩文习惯与西文相连：谚文之后的西文（如 `삼성Galaxy`），以及西文、数字之后的助词、词尾和量词（如 `API를`、`3개`）
+    Hangul,
+    /// 全角标点之后不加空格，如 `，bug`
+    AfterFullPunctuation,
+    /// 半角标点、空白之前不加空格，如 `中文!`
+    BeforeHalfPunctuation,
+    /// 半角符号之后不加空格，如 `@用户`
+    AfterHalfSymbol,
+    /// 全角标点之前不加空格，如 `example。`
+    BeforeFullPunctuation,
+    /// 货币符号与金额之间不加空格，如 `$五十`
+    Currency,
+    /// 行首不加空格
+    LineStart,
+    /// URL、邮箱、文件路径、版本号、@提及和 #话题 内部不加空格，两端照常处理
+    ProtectedTokens,
+    /// 把中日文字之间或之后的半角标点 `, . ! ? : ; ( )` 换成全角，如 `你好,世界` 改为 `你好，世界`；默认关闭
+    ///
+    /// `.` 只在两个中日文字之间转换，小数、省略号、URL 以及 `?.`、`!=` 这类代码写法保持不变。
+    FullWidthPunctuation,
+    /// 把全角字母、数字和 `％＋＝＠` 换成半角，如 `数字１２３和` 改为 `数字 123 和`；默认关闭
+    ///
+    /// 全角标点，以及换成半角后可能改变字符串、注释或标记含义的符号（如 `＂＼＄＜＆＊＃`）保持不变。
+    HalfWidthAlphanumerics,
+    /// 把中日韩文字与西文之间的多个空格、全角空格、不换行空格和制表符规整为一个半角空格；默认关闭
+    ///
+    /// 行首缩进、行尾空格（如 Markdown 的硬换行）保持不变，注释中连续的空白视为对齐，也保持不变。
+    NormalizeSpaces,
+}
+
+impl SpacingRule {
+    pub const ALL: [SpacingRule; 14] = [
+        SpacingRule::FullHalf,
+        SpacingRule::HalfFull,
+        SpacingRule::Digits,
+        SpacingRule::Hangul,
+        SpacingRule::AfterFullPunctuation,
+        SpacingRule::BeforeHalfPunctuation,
+        SpacingRule::AfterHalfSymbol,
+        SpacingRule::BeforeFullPunctuation,
+        SpacingRule::Currency,
+        SpacingRule::LineStart,
+        SpacingRule::ProtectedTokens,
+        SpacingRule::FullWidthPunctuation,
+        SpacingRule::HalfWidthAlphanumerics,
+        SpacingRule::NormalizeSpaces,
+    ];
+
+    /// 规则的稳定 ID
+    pub fn id(&self) -> &'static str {
+        match self {
+            SpacingRule::FullHalf => "full-half",
+            SpacingRule::HalfFull => "half-full",
+            SpacingRule::Digits => "digits",
+            SpacingRule::Hangul => "hangul",
+            SpacingRule::AfterFullPunctuation => "after-full-punctuation",
+            SpacingRule::BeforeHalfPunctuation => "before-half-punctuation",
+            SpacingRule::AfterHalfSymbol => "after-half-symbol",
+            SpacingRule::BeforeFullPunctuation => "before-full-punctuation",
+            SpacingRule::Currency => "currency",
+            SpacingRule::LineStart => "line-start",
+            SpacingRule::ProtectedTokens => "protected-tokens",
+            SpacingRule::FullWidthPunctuation => "full-width-punctuation",
+            SpacingRule::HalfWidthAlphanumerics => "half-width-alphanumerics",
+            SpacingRule::NormalizeSpaces => "normalize-spaces",
+        }
+    }
+
+    /// 规则在该语言下使用的默认字符集，不依赖字符集的规则返回空串
+    pub(crate) fn default_chars(&self, lang: Lang) -> &'static str {
+        match (self, lang) {
+            (SpacingRule::AfterFullPunctuation, Lang::Zh | Lang::Ko) => "，。！？：；“”‘’《》【】（）—…～·、",
+            (SpacingRule::BeforeFullPunctuation, Lang::Zh | Lang::Ko) => "，。！？：；“”‘’《》【】（）—…",
+            // 日文的括号、中点和波浪线自带间距，两侧都不加空格
+            (SpacingRule::AfterFullPunctuation | SpacingRule::BeforeFullPunctuation, Lang::Ja) => {
+                "、。，．！？：；・「」『』（）［］〔〕【】〈〉《》〜～…‥｡｢｣､･"
+            }
+            (SpacingRule::BeforeHalfPunctuation, _) => ",.!?:;\"'\n\r\t\\",
+            (SpacingRule::AfterHalfSymbol, _) => "\"'[{<@#%^&_|\\",
+            (SpacingRule::Currency, _) => "$¥€",
+            (
+                SpacingRule::FullHalf
+                | SpacingRule::HalfFull
+                | SpacingRule::Digits
+                | SpacingRule::Hangul
+                | SpacingRule::LineStart
+                | SpacingRule::ProtectedTokens
+                | SpacingRule::FullWidthPunctuation
+                | SpacingRule::HalfWidthAlphanumerics
+                | SpacingRule::NormalizeSpaces,
+                _,
+            ) => "",
+        }
+    }
+
+    /// 规则在该语言下是否默认启用
+    fn default_enabled(&self, lang: Lang) -> bool {
+        !matches!(
+            (self, lang),
+            (SpacingRule::Digits, Lang::Ja | Lang::Ko)
+                | (
+                    SpacingRule::FullWidthPunctuation
+                        | SpacingRule::HalfWidthAlphanumerics
+                        | SpacingRule::NormalizeSpaces,
+                    _
+                )
+        )
+    }
+}
+
+impl fmt::Display for SpacingRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for SpacingRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SpacingRule::ALL
+            .into_iter()
+            .find(|rule| rule.id() == s)
+            .ok_or_else(|| anyhow!("unknown spacing rule: {}", s))
+    }
+}
+
+/// 文本的语言，决定默认启用哪些规则以及各规则的字符集
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Lang {
+    /// 中文，默认
+    #[default]
+    Zh,
+    /// 日文：括号 `「」『』`、中点 `・`、波浪线 `〜` 等两侧不加空格，数字与假名、汉字之间不加空格
+    Ja,
+    /// 韩文：数字与谚文、汉字之间不加空格，谚文与西文的处理见 [`SpacingRule::Hangul`]
+    Ko,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 3] = [Lang::Zh, Lang::Ja, Lang::Ko];
+
+    /// 语言的稳定 ID
+    pub fn id(&self) -> &'static str {
+        match self {
+            Lang::Zh => "zh",
+            Lang::Ja => "ja",
+            Lang::Ko => "ko",
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Lang {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Lang::ALL
+            .into_iter()
+            .find(|lang| lang.id() == s)
+            .ok_or_else(|| anyhow!("unknown language: {}", s))
+    }
+}
+
+/// 插入的间隔
+///
+/// HTML 中窄空格和不换行空格写作字符引用 `&thinsp;`、`&nbsp;`，其他格式直接写入字符。
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Spacer {
+    /// 半角空格 U+0020，默认
+    #[default]
+    Space,
+    /// 窄空格 U+2009
+    Thin,
+    /// 不换行空格 U+00A0
+    NoBreak,
+    /// 原样写入的任意内容，如 `<span class="cjk-gap"></span>`；HTML 属性值和 `<title>` 等不能包含标签的位置改用半角空格
+    Custom(String),
+}
+
+impl Spacer {
+    /// 写入纯文本、代码等格式时的内容
+    pub fn as_str(&self) -> &str {
+        match self {
+            Spacer::Space => " ",
+            Spacer::Thin => "\u{2009}",
+            Spacer::NoBreak => "\u{a0}",
+            Spacer::Custom(spacer) => spacer,
+        }
+    }
+
+    /// 写入 HTML 时的内容，`markup` 表示该位置能否包含标签
+    pub(crate) fn html(&self, markup: bool) -> &str {
+        match self {
+            Spacer::Space => " ",
+            Spacer::Thin => "&thinsp;",
+            Spacer::NoBreak => "&nbsp;",
+            Spacer::Custom(spacer) if markup => spacer,
+            Spacer::Custom(_) => " ",
+        }
+    }
+}
+
+impl fmt::Display for Spacer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Spacer::Space => f.write_str("space"),
+            Spacer::Thin => f.write_str("thin"),
+            Spacer::NoBreak => f.write_str("nbsp"),
+            Spacer::Custom(spacer) => f.write_str(spacer),
+        }
+    }
+}
+
+/// `space`、`thin`、`nbsp`，其他非空内容原样作为间隔
+impl FromStr for Spacer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "" => Err(anyhow!("empty spacer")),
+            "space" => Ok(Spacer::Space),
+            "thin" => Ok(Spacer::Thin),
+            "nbsp" => Ok(Spacer::NoBreak),
+            _ => Ok(Spacer::Custom(s.to_string())),
+        }
+    }
+}
+
+/// 可单独选择是否处理的内容范围
+///
+/// 代码类格式（JS、Rust、CSS 等）中的注释、文档注释和字符串，以及 HTML、Markdown、纯文本中的正文。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+    /// 普通注释
+    Comments,
+    /// 文档注释，如 Rust 的 `///`、`//!` 和 JS 的 `/** */`
+    DocComments,
+    /// 字符串字面量
+    Strings,
+    /// 正文，如 HTML 文本节点、Markdown 段落和纯文本
+    Text,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [Scope::Comments, Scope::DocComments, Scope::Strings, Scope::Text];
+
+    /// 范围的稳定 ID
+    pub fn id(&self) -> &'static str {
+        match self {
+            Scope::Comments => "comments",
+            Scope::DocComments => "doc-comments",
+            Scope::Strings => "strings",
+            Scope::Text => "text",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.id() == s)
+            .ok_or_else(|| anyhow!("unknown scope: {}", s))
+    }
+}
+
+/// 标记所属的元素类型，决定标记两侧的文字是否视为相连
+///
+/// 各片段单独加空格，但行内元素两侧的文字相连，如 `中文<a>Link</a>` 会在 `<a>` 之前加空格；
+/// 作为分隔的元素两侧不加空格，默认为块级元素和换行，见 [`SpacingOptions::with_separators`]。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Element {
+    /// 行内元素，如 `<a>`、`<b>` 和 Markdown 的强调、链接
+    Inline,
+    /// 块级元素，如 `<p>`、`<div>`、`<li>`
+    Block,
+    /// 换行 `<br>`
+    LineBreak,
+    /// 行内代码，如 `<code>` 和 Markdown 的 `` `code` ``
+    Code,
+}
+
+impl Element {
+    pub const ALL: [Element; 4] = [Element::Inline, Element::Block, Element::LineBreak, Element::Code];
+
+    /// 元素类型的稳定 ID
+    pub fn id(&self) -> &'static str {
+        match self {
+            Element::Inline => "inline",
+            Element::Block => "block",
+            Element::LineBreak => "line-break",
+            Element::Code => "code",
+        }
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Element {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "br" => Ok(Element::LineBreak),
+            _ => Element::ALL
+                .into_iter()
+                .find(|element| element.id() == s)
+                .ok_or_else(|| anyhow!("unknown element type: {}", s)),
+        }
+    }
+}
+
+/// `spacing` 的规则配置
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::{Lang, SpacingOptions, SpacingRule, spacing_with_options};
+///
+/// let options = SpacingOptions::new().disable(SpacingRule::Currency);
+/// assert_eq!(spacing_with_options("价格$五十", &options), "价格 $ 五十");
+///
+/// let options = SpacingOptions::new().add_chars(SpacingRule::AfterHalfSymbol, "~");
+/// assert_eq!(spacing_with_options("目录~文档", &options), "目录 ~文档");
+///
+/// let options = SpacingOptions::new().add_exceptions(["C语言"]);
+/// assert_eq!(spacing_with_options("学习C语言和Rust", &options), "学习 C语言和 Rust");
+///
+/// let options = SpacingOptions::new().with_lang(Lang::Ja);
+/// assert_eq!(spacing_with_options("第3章「Rust」をGitで管理", &options), "第3章「Rust」を Git で管理");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpacingOptions {
+    lang: Lang,
+    enabled: BTreeSet<SpacingRule>,
+    after_full_punctuation: BTreeSet<char>,
+    before_half_punctuation: BTreeSet<char>,
+    after_half_symbol: BTreeSet<char>,
+    before_full_punctuation: BTreeSet<char>,
+    currency: BTreeSet<char>,
+    exceptions: BTreeSet<String>,
+    scopes: BTreeSet<Scope>,
+    separators: BTreeSet<Element>,
+    spacer: Spacer,
+    unspacing: bool,
+}
+
+impl Default for SpacingOptions {
+    fn default() -> Self {
+        Self::defaults(Lang::default())
+    }
+}
+
+impl SpacingOptions {
+    /// 默认配置，中文规则集
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn defaults(lang: Lang) -> Self {
+        let chars = |rule: SpacingRule| rule.default_chars(lang).chars().collect();
+        Self {
+            lang,
+            enabled: SpacingRule::ALL
+                .into_iter()
+                .filter(|rule| rule.default_enabled(lang))
+                .collect(),
+            after_full_punctuation: chars(SpacingRule::AfterFullPunctuation),
+            before_half_punctuation: chars(SpacingRule::BeforeHalfPunctuation),
+            after_half_symbol: chars(SpacingRule::AfterHalfSymbol),
+            before_full_punctuation: chars(SpacingRule::BeforeFullPunctuation),
+            currency: chars(SpacingRule::Currency),
+            exceptions: BTreeSet::new(),
+            scopes: Scope::ALL.into_iter().collect(),
+            separators: [Element::Block, Element::LineBreak].into_iter().collect(),
+            spacer: Spacer::default(),
+            unspacing: false,
+        }
+    }
+
+    /// 切换到该语言的规则集
+    ///
+    /// 仍为原语言默认值的规则开关和字符集换成新语言的默认值，单独修改过的保持不变。
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        let (old, new) = (Self::defaults(self.lang), Self::defaults(lang));
+        for rule in SpacingRule::ALL {
+            if self.is_enabled(rule) == old.is_enabled(rule) {
+                self = self.set_rule(rule, new.is_enabled(rule));
+            }
+            if self.chars(rule) == old.chars(rule)
+                && let (Some(chars), Some(new_chars)) = (self.chars_mut(rule), new.chars(rule))
+            {
+                *chars = new_chars.clone();
+            }
+        }
+        self.lang = lang;
+        self
+    }
+
+    pub fn enable(self, rule: SpacingRule) -> Self {
+        self.set_rule(rule, true)
+    }
+
+    pub fn disable(self, rule: SpacingRule) -> Self {
+        self.set_rule(rule, false)
+    }
+
+    pub fn set_rule(mut self, rule: SpacingRule, enabled: bool) -> Self {
+        if enabled {
+            self.enabled.insert(rule);
+        } else {
+            self.enabled.remove(&rule);
+        }
+        self
+    }
+
+    /// 替换规则使用的字符集，对不依赖字符集的规则无效
+    pub fn with_chars(mut self, rule: SpacingRule, chars: &str) -> Self {
+        if let Some(set) = self.chars_mut(rule) {
+            *set = chars.chars().collect();
+        }
+        self
+    }
+
+    /// 向规则使用的字符集追加字符，对不依赖字符集的规则无效
+    pub fn add_chars(mut self, rule: SpacingRule, chars: &str) -> Self {
+        if let Some(set) = self.chars_mut(rule) {
+            set.extend(chars.chars());
+        }
+        self
+    }
+
+    /// 从规则使用的字符集中移除字符
+    pub fn remove_chars(mut self, rule: SpacingRule, chars: &str) -> Self {
+        if let Some(set) = self.chars_mut(rule) {
+            for c in chars.chars() {
+                set.remove(&c);
+            }
+        }
+        self
+    }
+
+    /// 添加例外词，例外词内部不插入空格，词的两端照常处理
+    pub fn add_exceptions<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exceptions
+            .extend(words.into_iter().map(Into::into).filter(|word| !word.is_empty()));
+        self
+    }
+
+    /// 只处理给定范围内的内容，见 [`Scope`]
+    pub fn with_scopes(mut self, scopes: impl IntoIterator<Item = Scope>) -> Self {
+        self.scopes = scopes.into_iter().collect();
+        self
+    }
+
+    /// 两侧的文字不视为相连的元素类型，见 [`Element`]
+    pub fn with_separators(mut self, separators: impl IntoIterator<Item = Element>) -> Self {
+        self.separators = separators.into_iter().collect();
+        self
+    }
+
+    /// 插入的间隔，见 [`Spacer`]
+    pub fn with_spacer(mut self, spacer: Spacer) -> Self {
+        self.spacer = spacer;
+        self
+    }
+
+    /// 反向处理：去掉规则会插入空格处已有的单个半角空格，而不是插入空格
+    ///
+    /// `spacing_with_options`、`process_*_with_options` 和 `analyze_with_options` 都按此处理。
+    pub fn with_unspacing(mut self, unspacing: bool) -> Self {
+        self.unspacing = unspacing;
+        self
+    }
+
+    pub fn lang(&self) -> Lang {
+        self.lang
+    }
+
+    pub fn exceptions(&self) -> &BTreeSet<String> {
+        &self.exceptions
+    }
+
+    pub fn scopes(&self) -> &BTreeSet<Scope> {
+        &self.scopes
+    }
+
+    pub fn separators(&self) -> &BTreeSet<Element> {
+        &self.separators
+    }
+
+    pub fn is_separator(&self, element: Element) -> bool {
+        self.separators.contains(&element)
+    }
+
+    pub fn spacer(&self) -> &Spacer {
+        &self.spacer
+    }
+
+    pub fn is_unspacing(&self) -> bool {
+        self.unspacing
+    }
+
+    pub fn in_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn is_enabled(&self, rule: SpacingRule) -> bool {
+        self.enabled.contains(&rule)
+    }
+
+    /// 规则使用的字符集，不依赖字符集的规则返回 `None`
+    pub fn chars(&self, rule: SpacingRule) -> Option<&BTreeSet<char>> {
+        match rule {
+            SpacingRule::AfterFullPunctuation => Some(&self.after_full_punctuation),
+            SpacingRule::BeforeHalfPunctuation => Some(&self.before_half_punctuation),
+            SpacingRule::AfterHalfSymbol => Some(&self.after_half_symbol),
+            SpacingRule::BeforeFullPunctuation => Some(&self.before_full_punctuation),
+            SpacingRule::Currency => Some(&self.currency),
+            SpacingRule::FullHalf
+            | SpacingRule::HalfFull
+            | SpacingRule::Digits
+            | SpacingRule::Hangul
+            | SpacingRule::LineStart
+            | SpacingRule::ProtectedTokens
+            | SpacingRule::FullWidthPunctuation
+            | SpacingRule::HalfWidthAlphanumerics
+            | SpacingRule::NormalizeSpaces => None,
+        }
+    }
+
+    fn chars_mut(&mut self, rule: SpacingRule) -> Option<&mut BTreeSet<char>> {
+        match rule {
+            SpacingRule::AfterFullPunctuation => Some(&mut self.after_full_punctuation),
+            SpacingRule::BeforeHalfPunctuation => Some(&mut self.before_half_punctuation),
+            SpacingRule::AfterHalfSymbol => Some(&mut self.after_half_symbol),
+            SpacingRule::BeforeFullPunctuation => Some(&mut self.before_full_punctuation),
+            SpacingRule::Currency => Some(&mut self.currency),
+            SpacingRule::FullHalf
+            | SpacingRule::HalfFull
+            | SpacingRule::Digits
+            | SpacingRule::Hangul
+            | SpacingRule::LineStart
+            | SpacingRule::ProtectedTokens
+            | SpacingRule::FullWidthPunctuation
+            | SpacingRule::HalfWidthAlphanumerics
+            | SpacingRule::NormalizeSpaces => None,
+        }
+    }
+
+    /// 规则已启用，且字符在规则的字符集中
+    pub(crate) fn matches(&self, rule: SpacingRule, c: char) -> bool {
+        self.is_enabled(rule) && self.chars(rule).is_some_and(|set| set.contains(&c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_ids_round_trip() {
+        for rule in SpacingRule::ALL {
+            assert_eq!(rule.id().parse::<SpacingRule>().unwrap(), rule);
+        }
+        assert!("no-such-rule".parse::<SpacingRule>().is_err());
+        for scope in Scope::ALL {
+            assert_eq!(scope.id().parse::<Scope>().unwrap(), scope);
+        }
+        for lang in Lang::ALL {
+            assert_eq!(lang.id().parse::<Lang>().unwrap(), lang);
+        }
+        for element in Element::ALL {
+            assert_eq!(element.id().parse::<Element>().unwrap(), element);
+        }
+        assert_eq!("br".parse::<Element>().unwrap(), Element::LineBreak);
+    }
+
+    #[test]
+    fn test_with_lang() {
+        let options = SpacingOptions::new()
+            .disable(SpacingRule::Currency)
+            .with_chars(SpacingRule::BeforeFullPunctuation, "，")
+            .with_lang(Lang::Ja);
+        assert_eq!(options.lang(), Lang::Ja);
+        assert!(!options.is_enabled(SpacingRule::Digits));
+        assert!(!options.is_enabled(SpacingRule::Currency));
+        assert!(options.matches(SpacingRule::AfterFullPunctuation, '「'));
+        assert!(!options.matches(SpacingRule::BeforeFullPunctuation, '「'));
+
+        let options = options.with_lang(Lang::Zh);
+        assert!(options.is_enabled(SpacingRule::Digits));
+        assert!(!options.matches(SpacingRule::AfterFullPunctuation, '「'));
+        assert_eq!(options.clone().with_lang(Lang::Ja).with_lang(Lang::Zh), options);
+        assert_eq!(SpacingOptions::new().with_lang(Lang::Ja).with_lang(Lang::Zh), SpacingOptions::new());
+
+        let options = SpacingOptions::new().with_lang(Lang::Ko);
+        assert!(options.is_enabled(SpacingRule::Hangul));
+        assert!(!options.is_enabled(SpacingRule::Digits));
+    }
+
+    #[test]
+    fn test_builder() {
+        let options = SpacingOptions::new()
+            .disable(SpacingRule::Currency)
+            .with_chars(SpacingRule::AfterHalfSymbol, "@#");
+        assert!(!options.is_enabled(SpacingRule::Currency));
+        assert!(options.is_enabled(SpacingRule::FullHalf));
+        assert!(!options.matches(SpacingRule::Currency, '$'));
+        assert!(options.matches(SpacingRule::AfterHalfSymbol, '@'));
+        assert!(!options.matches(SpacingRule::AfterHalfSymbol, '&'));
+        assert_eq!(options.chars(SpacingRule::FullHalf), None);
+
+        let options = options.enable(SpacingRule::Currency).remove_chars(SpacingRule::Currency, "$");
+        assert!(!options.matches(SpacingRule::Currency, '$'));
+        assert!(options.matches(SpacingRule::Currency, '¥'));
+
+        let options = SpacingOptions::new()
+            .add_exceptions(["iPhone", ""])
+            .with_scopes([Scope::Comments]);
+        assert_eq!(options.exceptions().len(), 1);
+        assert!(options.in_scope(Scope::Comments));
+        assert!(!options.in_scope(Scope::Strings));
+    }
+}
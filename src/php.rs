@@ -1,147 +1,104 @@
 // src/php.rs
+use std::ops::Range;
+
 use anyhow::Result;
 
 use pest::Parser;
 use pest::iterators::Pair;
 use pest_derive::Parser;
 
-// Import the HTML processor
-use crate::html; // Assuming html.rs provides a public `process` function
+use crate::html;
+use crate::{ParseError, SpacingOptions};
+use crate::edit::Context;
+use crate::segment::{CommentStyle, Segment, SegmentKind, Segments};
 
 #[derive(Parser)]
 #[grammar = "grammar/php.pest"]
 struct PhpParser;
 
-fn spacing(input: &str) -> String {
-    dbg!(&input);
-    if let Ok(parsed) = html::process(input) {
-        dbg!(&parsed);
-        return parsed;
-    }
-    input.to_string()
+/// Pushes `range` of the input, which may hold markup, as `kind`
+fn push_html(segments: &mut Segments, input: &str, range: Range<usize>, kind: SegmentKind) -> Result<()> {
+    let html = html::segment(&input[range.clone()]).map_err(|e| ParseError::within(e, input, range.start))?;
+    segments.extend(range.start, html, Some(kind));
+    Ok(())
 }
 
-fn process_string(pair: Pair<Rule>) -> String {
-    let mut result = String::new();
+fn push_string(segments: &mut Segments, input: &str, pair: Pair<Rule>) -> Result<()> {
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
-            Rule::heredoc_plain_chunk => {
-                result.push_str(&spacing(inner_pair.as_str()));
-            }
-            Rule::nowdoc_body_content => {
-                result.push_str(&spacing(inner_pair.as_str()));
-            }
-            Rule::php_double_quoted_string => {
-                result.push('"');
-                for dq_inner in inner_pair.into_inner() {
-                    match dq_inner.as_rule() {
-                        Rule::php_dq_normal_text => {
-                            result.push_str(&spacing(dq_inner.as_str()));
-                        }
-                        _ => {
-                            result.push_str(dq_inner.as_str());
-                        }
-                    }
-                }
-                result.push('"');
+            Rule::heredoc_plain_chunk | Rule::nowdoc_body_content => {
+                let span = inner_pair.as_span();
+                push_html(segments, input, span.start()..span.end(), SegmentKind::StringLiteral { quote: None })?;
             }
-            Rule::php_single_quoted_string => {
-                result.push('\'');
-                for sq_inner in inner_pair.into_inner() {
-                    match sq_inner.as_rule() {
-                        Rule::php_sq_normal_text => {
-                            result.push_str(&spacing(sq_inner.as_str()));
-                        }
-                        _ => {
-                            result.push_str(sq_inner.as_str());
-                        }
+            Rule::php_double_quoted_string | Rule::php_single_quoted_string => {
+                let quote = if inner_pair.as_rule() == Rule::php_double_quoted_string { '"' } else { '\'' };
+                let kind = SegmentKind::StringLiteral { quote: Some(quote) };
+                for quoted_inner in inner_pair.into_inner() {
+                    if matches!(quoted_inner.as_rule(), Rule::php_dq_normal_text | Rule::php_sq_normal_text) {
+                        let span = quoted_inner.as_span();
+                        push_html(segments, input, span.start()..span.end(), kind)?;
                     }
                 }
-                result.push('\'');
             }
             // variable
-            _ => {
-                result.push_str(inner_pair.as_str());
-            }
+            _ => {}
         }
     }
-    result
+    Ok(())
 }
 
 /// Processes a PHP string, extracting comments and strings,
 /// and delegating HTML sections to html::process.
 pub fn process(input: &str) -> Result<String> {
-    let pairs = PhpParser::parse(Rule::program, input)?;
+    process_with_options(input, &SpacingOptions::default())
+}
 
-    let mut result = Vec::new();
-dbg!(&pairs);
-    // Define a recursive helper function to process pairs
-    fn parse_pair(result: &mut Vec<String>, pair: Pair<Rule>) -> Result<()> {
+pub fn process_with_options(input: &str, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(input, &segment(input)?))
+}
+
+pub(crate) fn segment(input: &str) -> Result<Vec<Segment>> {
+    let pairs = PhpParser::parse(Rule::program, input).map_err(ParseError::from)?;
 
+    let mut segments = Segments::new();
+    // Define a recursive helper function to process pairs
+    fn parse_pair(segments: &mut Segments, input: &str, pair: Pair<Rule>) -> Result<()> {
+        let (start, end) = (pair.as_span().start(), pair.as_span().end());
         match pair.as_rule() {
-            // Top-level structure: descend into inner chunks
-            Rule::program => {
+            // Descend into the chunks and the body of PHP code
+            Rule::program | Rule::php_chunk | Rule::php_script_body | Rule::php_comment => {
                 for inner_pair in pair.into_inner() {
-                    if inner_pair.as_rule() != Rule::EOI {
-                        // Skip EOI for cleaner output
-                        parse_pair(result, inner_pair)?;
-                    }
+                    parse_pair(segments, input, inner_pair)?;
                 }
             }
-            // HTML Chunk: Process using html::process
+            // HTML Chunk: segmented by the HTML processor
             Rule::html_chunk => {
-                let html_str = pair.as_str();
-                // Call the HTML processor from the html module
-                // Assuming html::process correctly handles HTML fragments
-                match html::process(html_str) {
-                    Ok(processed_html) => result.push(processed_html),
-                    Err(e) => {
-                        eprintln!(
-                            "Warning: HTML processing failed for chunk: {:?}. Error: {}",
-                            html_str, e
-                        );
-                        // Fallback: push original HTML string if processing fails
-                        result.push(html_str.to_string());
-                    }
-                }
-            }
-            // PHP Chunk: Process tags and inner body
-            Rule::php_chunk => {
-                for inner_pair in pair.into_inner() {
-                    parse_pair(result, inner_pair)?;
-                }
-            }
-            // Keep PHP tags as they are
-            Rule::php_start_tag | Rule::php_end_tag => {
-                result.push(pair.as_str().to_string());
-            }
-            // Explicitly handle the body content matched by php_script_body
-            Rule::php_script_body => {
-                for inner_pair in pair.into_inner() {
-                    parse_pair(result, inner_pair)?;
-                }
+                let html = html::segment(pair.as_str()).map_err(|e| ParseError::within(e, input, start))?;
+                segments.extend(start, html, None)
             }
-            // Extract PHP Comments (keeping delimiters for context)
-            Rule::php_comment => {
-                // You might want different processing here, e.g., just extract content
-                result.push(spacing(pair.as_str()));
+            // Comments may hold markup too
+            Rule::php_line_comment => {
+                let delimiter = if pair.as_str().starts_with('#') { 1 } else { 2 };
+                let kind = SegmentKind::Comment { style: CommentStyle::Line };
+                push_html(segments, input, start + delimiter..end, kind)?;
             }
-            Rule::php_string => result.push(process_string(pair)),
-            // Catch unhandled rules during development
-            _ => {
-                result.push(pair.as_str().to_string());
+            Rule::php_block_comment => {
+                let kind = SegmentKind::Comment { style: CommentStyle::Block };
+                push_html(segments, input, start + 2..end - 2, kind)?;
             }
+            Rule::php_string => push_string(segments, input, pair)?,
+            // Tags, variables and other code are kept as they are
+            _ => {}
         }
         Ok(())
     }
 
     // Start processing from the top-level pairs
     for pair in pairs {
-        parse_pair(&mut result, pair)?;
+        parse_pair(&mut segments, input, pair)?;
     }
 
-    // Join the processed parts without extra spaces
-    Ok(result.join(""))
+    Ok(segments.finish(input.len()))
 }
 
 // --- Basic Tests ---
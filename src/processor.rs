@@ -0,0 +1,180 @@
+use std::sync::{Arc, LazyLock, RwLock, RwLockReadGuard};
+
+use anyhow::{Result, bail};
+
+use crate::edit::Context;
+use crate::{Format, Segment, SpacingOptions};
+
+/// 格式处理器：把输入切分成 [`Segment`]，加空格、忽略指令等由统一的流程完成
+///
+/// 内置格式都以处理器的形式注册，其他格式可通过 [`register`] 加入，
+/// 之后 [`Format`] 的名称解析、[`Format::from_path`] 和命令行都能识别。
+/// [`process`](crate::process) 的结果与 [`analyze`](crate::analyze) 报告的修改都由切分结果得出，二者总是一致。
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::{Format, FormatProcessor, Segment, SegmentKind, process, register};
+///
+/// /// 以 `;` 开头的行是注释
+/// struct Ini;
+///
+/// impl FormatProcessor for Ini {
+///     fn name(&self) -> &str {
+///         "ini"
+///     }
+///
+///     fn extensions(&self) -> &[&str] {
+///         &["ini"]
+///     }
+///
+///     fn segment(&self, input: &str) -> anyhow::Result<Vec<Segment>> {
+///         let mut segments = Vec::new();
+///         let mut start = 0;
+///         for line in input.split_inclusive('\n') {
+///             let kind = if line.starts_with(';') { SegmentKind::Text } else { SegmentKind::Code };
+///             segments.push(Segment::new(kind, start..start + line.len()));
+///             start += line.len();
+///         }
+///         Ok(segments)
+///     }
+/// }
+///
+/// let format = register(Ini).unwrap();
+/// assert_eq!("ini".parse::<Format>().unwrap(), format);
+/// assert_eq!(process("; 注释Comment\nkey=值value", format).unwrap(), "; 注释 Comment\nkey=值value");
+/// ```
+pub trait FormatProcessor: Send + Sync {
+    /// 格式名称，用于配置文件和命令行
+    fn name(&self) -> &str;
+
+    /// 对应的文件扩展名，不含 `.`
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    /// 切分 `input`，片段按顺序首尾相接，覆盖整个输入
+    fn segment(&self, input: &str) -> Result<Vec<Segment>>;
+}
+
+impl dyn FormatProcessor {
+    /// 按规则配置处理 `input`，即按切分结果加空格
+    ///
+    /// 这不是 trait 的方法，实现者无法改写，结果总与 [`analyze`](crate::analyze) 报告的修改一致。
+    pub fn process(&self, input: &str, options: &SpacingOptions) -> Result<String> {
+        Ok(Context::new(options).run(input, &self.segment(input)?))
+    }
+}
+
+/// The processor of a built-in format.
+struct Builtin(Format);
+
+impl FormatProcessor for Builtin {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.0.extensions()
+    }
+
+    fn segment(&self, input: &str) -> Result<Vec<Segment>> {
+        self.0.segment(input)
+    }
+}
+
+type Registered = (Format, Arc<dyn FormatProcessor>);
+
+/// Registered processors with their formats, built-in ones first.
+static REGISTRY: LazyLock<RwLock<Vec<Registered>>> = LazyLock::new(|| {
+    let builtin = Format::ALL.map(|format| (format, Arc::new(Builtin(format)) as Arc<dyn FormatProcessor>));
+    RwLock::new(builtin.into())
+});
+
+fn registry() -> RwLockReadGuard<'static, Vec<Registered>> {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 注册格式处理器，返回代表它的 [`Format`]；名称与已有的格式或其别名重复时返回错误
+pub fn register(processor: impl FormatProcessor + 'static) -> Result<Format> {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    let name = processor.name();
+    if name.is_empty() {
+        bail!("format name is empty");
+    }
+    if Format::from_alias(name).is_some() || registry.iter().any(|(format, _)| format.name().eq_ignore_ascii_case(name)) {
+        bail!("format {} is already registered", name);
+    }
+    // Formats are `Copy`, so the name lives as long as the registry; it is
+    // only leaked once it is known to be new
+    let format = Format::Custom(Box::leak(name.into()));
+    registry.push((format, Arc::new(processor)));
+    Ok(format)
+}
+
+/// 所有已注册的处理器，内置格式在前
+pub fn processors() -> Vec<(Format, Arc<dyn FormatProcessor>)> {
+    registry().clone()
+}
+
+/// 格式对应的处理器
+pub fn processor(format: Format) -> Option<Arc<dyn FormatProcessor>> {
+    registry().iter().find(|(registered, _)| *registered == format).map(|(_, processor)| Arc::clone(processor))
+}
+
+/// The registered format named `name`, ignoring case.
+pub(crate) fn by_name(name: &str) -> Option<Format> {
+    registry().iter().map(|&(format, _)| format).find(|format| format.name().eq_ignore_ascii_case(name))
+}
+
+/// The format for a file extension, with processors registered later taking
+/// precedence, so that one can claim an extension of a built-in format.
+pub(crate) fn by_extension(extension: &str) -> Option<Format> {
+    registry()
+        .iter()
+        .rev()
+        .find(|(_, processor)| processor.extensions().iter().any(|e| e.eq_ignore_ascii_case(extension)))
+        .map(|&(format, _)| format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SegmentKind, process};
+
+    struct Lines(&'static str);
+
+    impl FormatProcessor for Lines {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["lines"]
+        }
+
+        fn segment(&self, input: &str) -> Result<Vec<Segment>> {
+            Ok(vec![Segment::new(SegmentKind::Comment { style: crate::CommentStyle::Line }, 0..input.len())])
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let format = register(Lines("test-lines")).unwrap();
+        assert_eq!(format.name(), "test-lines");
+        assert_eq!("Test-Lines".parse::<Format>().unwrap(), format);
+        assert_eq!(Format::from_path(std::path::Path::new("a.LINES")), Some(format));
+        let options = crate::SpacingOptions::default();
+        assert_eq!(processor(format).unwrap().process("注释Comment", &options).unwrap(), "注释 Comment");
+        assert_eq!(process("注释Comment", format).unwrap(), "注释 Comment");
+        let options = crate::SpacingOptions::new().with_scopes([crate::Scope::Text]);
+        assert_eq!(crate::process_with_options("注释Comment", format, &options).unwrap(), "注释Comment");
+        let edits = crate::analyze("注释Comment", format).unwrap();
+        assert_eq!((edits.len(), edits[0].offset), (1, "注释".len()));
+
+        assert!(register(Lines("test-lines")).is_err());
+        assert!(register(Lines("markdown")).is_err());
+        assert!(register(Lines("md")).is_err());
+        assert!(register(Lines("")).is_err());
+    }
+}
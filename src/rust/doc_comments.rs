@@ -1,3 +1,4 @@
+use crate::edit::Context;
 use crate::markdown;
 use regex::Regex;
 
@@ -44,7 +45,9 @@ impl DocCommentBlock {
         (prefixes, contents)
     }
 
-    pub fn spacing(&self) -> String {
+    /// Spaces the comment as markdown. Offsets recorded in `ctx` are relative
+    /// to the start of the first raw line.
+    pub(crate) fn spacing(&self, ctx: &mut Context) -> String {
         if self.raw_lines.is_empty() {
             return "".to_string();
         }
@@ -52,9 +55,23 @@ impl DocCommentBlock {
         // Call the helper method to get prefixes and contents
         let (prefixes, contents) = self.split_prefix_and_content();
 
+        // Where each line's content starts, in the joined block and in the raw lines
+        let mut line_starts: Vec<(usize, usize)> = Vec::with_capacity(contents.len());
+        let (mut block_pos, mut raw_pos) = (0, 0);
+        for ((prefix, content), raw_line) in prefixes.iter().zip(&contents).zip(&self.raw_lines) {
+            line_starts.push((block_pos, raw_pos + prefix.len()));
+            block_pos += content.len();
+            raw_pos += raw_line.len();
+        }
+        let to_raw = |offset: usize| {
+            let line = line_starts.partition_point(|&(block_start, _)| block_start <= offset);
+            let (block_start, raw_start) = line_starts[line.saturating_sub(1)];
+            raw_start + offset - block_start
+        };
+
         // Join contents, process with markdown, and split back into lines
         let content_block = contents.join("");
-        let spaced_content_block = markdown::process(&content_block).unwrap_or(content_block);
+        let spaced_content_block = ctx.remapped(to_raw, |ctx| markdown::process_with_context(&content_block, ctx));
         let spaced_content_lines: Vec<String> = spaced_content_block
             .lines()
             .map(|s| s.to_string())
@@ -75,15 +92,16 @@ impl DocCommentBlock {
 
         let mut result = result_lines.join("\n");
         // Check if the original input had a trailing newline on the last line
-        if let Some(last_line) = self.raw_lines.last() {
-            if !last_line.is_empty() && last_line.ends_with('\n') {
-                 // Append the newline only if the joined result doesn't already end with one
-                 // (markdown::process might potentially add one in some cases)
-                 if !result.ends_with('\n') {
-                     result.push('\n');
-                 }
+        if let Some(last_line) = self.raw_lines.last()
+            && !last_line.is_empty()
+            && last_line.ends_with('\n')
+        {
+            // Append the newline only if the joined result doesn't already end with one
+            // (markdown::process might potentially add one in some cases)
+            if !result.ends_with('\n') {
+                result.push('\n');
             }
-         }
+        }
         result
     }
 }
@@ -371,7 +389,8 @@ this is another comment line
             CommentStyle::LineOuter,
             vec!["/// This is一条注释\n".to_string(), "/// This is another comment\n".to_string()],
         );
-        let result = block.spacing();
+        let options = crate::SpacingOptions::default();
+        let result = block.spacing(&mut Context::new(&options));
         assert_eq!(result, "/// This is 一条注释\n/// This is another comment\n");
     }
 }
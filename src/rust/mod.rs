@@ -3,7 +3,9 @@ mod doc_comments;
 use pest::Parser;
 use pest_derive::Parser;
 
-use crate::{rust::doc_comments::*, spacing};
+use crate::edit::Context;
+use crate::segment::{self, Segment, SegmentKind, Segments};
+use crate::{ParseError, SpacingOptions, rust::doc_comments::*};
 use anyhow::Result;
 use pest::iterators::Pair;
 
@@ -13,72 +15,83 @@ use pest::iterators::Pair;
 struct RustParser;
 
 pub fn process(input: &str) -> Result<String> {
-    let r = RustParser::parse(Rule::program, input)?;
-    let mut result: Vec<String> = Vec::new();
+    process_with_options(input, &SpacingOptions::default())
+}
 
-    fn parse_pair(result: &mut Vec<String>, pair: Pair<Rule>) {
-        match pair.as_rule() {
-            Rule::line_outer_doc_comment_block => {
-                let raw_lines: Vec<String> =
-                    pair.into_inner().map(|p| p.as_str().to_string()).collect();
-                let block = DocCommentBlock::new(CommentStyle::LineOuter, raw_lines);
-                let spaced_content = block.spacing();
-                result.push(spaced_content);
-            }
-            Rule::line_inner_doc_comment_block => {
-                let raw_lines: Vec<String> =
-                    pair.into_inner().map(|p| p.as_str().to_string()).collect();
-                let block = DocCommentBlock::new(CommentStyle::LineInner, raw_lines);
-                let spaced_content = block.spacing();
-                result.push(spaced_content);
-            }
-            Rule::block_outer_doc_comment => {
-                let inner_pairs = pair.into_inner();
-                let raw_lines: Vec<String> = inner_pairs.map(|p| p.as_str().to_string()).collect();
+pub fn process_with_options(input: &str, options: &SpacingOptions) -> Result<String> {
+    Ok(Context::new(options).run(input, &segment(input)?))
+}
 
-                let block = DocCommentBlock::new(CommentStyle::BlockOuter, raw_lines);
-                let spaced_inner_content = block.spacing();
+pub(crate) fn segment(input: &str) -> Result<Vec<Segment>> {
+    let r = RustParser::parse(Rule::program, input).map_err(ParseError::from)?;
+    let mut segments = Segments::new();
 
-                let formatted_comment = format!("{}{}{}", "/**", spaced_inner_content, "*/");
-                result.push(formatted_comment);
+    fn parse_pair(segments: &mut Segments, pair: Pair<Rule>) {
+        let (start, end) = (pair.as_span().start(), pair.as_span().end());
+        match pair.as_rule() {
+            // Doc comments keep their delimiters, which are part of each line
+            Rule::line_outer_doc_comment_block
+            | Rule::line_inner_doc_comment_block
+            | Rule::block_outer_doc_comment
+            | Rule::block_inner_doc_comment => {
+                segments.push(SegmentKind::DocMarkdown, start..end);
             }
-            Rule::block_inner_doc_comment => {
-                let inner_pairs = pair.into_inner();
-
-                let raw_lines: Vec<String> = inner_pairs.map(|p| p.as_str().to_string()).collect();
-
-                let block = DocCommentBlock::new(CommentStyle::BlockInner, raw_lines);
-                let spaced_inner_content = block.spacing();
-
-                let formatted_comment = format!("{}{}{}", "/*!", spaced_inner_content, "*/");
-                result.push(formatted_comment);
+            Rule::line_comment => {
+                segments.push(SegmentKind::Comment { style: segment::CommentStyle::Line }, start + 2..end);
             }
-            Rule::line_comment | Rule::block_comment => {
-                result.push(spacing(pair.as_str()));
+            Rule::block_comment => {
+                segments.push(SegmentKind::Comment { style: segment::CommentStyle::Block }, start + 2..end - 2);
             }
-            Rule::comment => {
+            Rule::comment | Rule::string | Rule::program => {
                 for inner_pair in pair.into_inner() {
-                    parse_pair(result, inner_pair);
+                    parse_pair(segments, inner_pair);
                 }
             }
-            Rule::string => {
-                result.push(spacing(pair.as_str()));
-            }
-            Rule::program => {
-                for inner_pair in pair.into_inner() {
-                    parse_pair(result, inner_pair);
+            // The content of the literal, between the quotes after `b`, `r` and
+            // the hashes of a raw string
+            Rule::string_literal | Rule::byte_string | Rule::raw_string | Rule::raw_byte_string => {
+                let text = pair.as_str();
+                if let (Some(open), Some(close)) = (text.find('"'), text.rfind('"')) {
+                    segments.push(SegmentKind::StringLiteral { quote: Some('"') }, start + open + 1..start + close);
                 }
             }
-            _ => {
-                result.push(pair.as_str().to_owned());
-            }
+            _ => {}
         }
     }
     for pair in r {
-        parse_pair(&mut result, pair);
+        parse_pair(&mut segments, pair);
     }
 
-    Ok(result.join(""))
+    Ok(segments.finish(input.len()))
+}
+
+/// Spaces a doc comment, as found in a `DocMarkdown` segment, as Markdown.
+pub(crate) fn doc_markdown(text: &str, ctx: &mut Context) -> String {
+    let style = if text.starts_with("///") {
+        CommentStyle::LineOuter
+    } else if text.starts_with("//!") {
+        CommentStyle::LineInner
+    } else if text.starts_with("/*!") {
+        CommentStyle::BlockInner
+    } else {
+        CommentStyle::BlockOuter
+    };
+    let (prefix, content) = match style {
+        CommentStyle::LineOuter | CommentStyle::LineInner => ("", text),
+        CommentStyle::BlockOuter | CommentStyle::BlockInner => (&text[..3], &text[3..text.len() - 2]),
+    };
+    let mut raw_lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+    // The grammar gives a block comment a last line, empty after a line break
+    if !prefix.is_empty() && (content.is_empty() || content.ends_with('\n')) {
+        raw_lines.push(String::new());
+    }
+    let block = DocCommentBlock::new(style, raw_lines);
+    let spaced = ctx.nested(prefix.len(), |ctx| block.spacing(ctx));
+    if prefix.is_empty() {
+        spaced
+    } else {
+        format!("{}{}*/", prefix, spaced)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,359 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::edit::Context;
+use crate::{Element, Format, Scope, rust};
+
+/// 输入切分后的一段，按 [`SegmentKind`] 决定是否加空格、归入哪个 [`Scope`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    /// 在输入中的字节范围；注释和字符串不含定界符，定界符属于前后的 `Code`
+    pub range: Range<usize>,
+    pub markup: Markup,
+}
+
+/// 片段的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SegmentKind {
+    /// 代码、标签等原样保留的内容
+    Code,
+    Comment { style: CommentStyle },
+    /// 字符串字面量的内容，heredoc 等没有引号的为 `None`
+    StringLiteral { quote: Option<char> },
+    /// 正文，如 HTML 文本和 Markdown 段落
+    Text,
+    /// 按 Markdown 处理的文档注释，范围包含 `///`、`/**` 等定界符
+    DocMarkdown,
+    /// 标签、强调符号等标记，原样保留；不作分隔的元素两侧的文字视为相连，见 [`Element`]
+    Tag { element: Element, open: bool },
+    /// 行内代码的内容，原样保留，但与两侧的文字之间按规则加空格
+    InlineCode,
+}
+
+/// 注释的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommentStyle {
+    /// `//`、`#`
+    Line,
+    /// `/* */`
+    Block,
+    /// `/** */` 等文档注释，归入 [`Scope::DocComments`]
+    Doc,
+    /// `<!-- -->`
+    Html,
+}
+
+/// 片段内容的书写方式，决定间隔字符如何写出
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Markup {
+    #[default]
+    Plain,
+    /// HTML，间隔字符写成字符引用；`tags` 表示该位置能否包含标签，属性值和 `<title>` 中不能
+    Html { tags: bool },
+}
+
+impl Segment {
+    pub fn new(kind: SegmentKind, range: Range<usize>) -> Self {
+        Self { kind, range, markup: Markup::Plain }
+    }
+
+    pub fn with_markup(mut self, markup: Markup) -> Self {
+        self.markup = markup;
+        self
+    }
+
+    /// 片段所属的内容范围，原样保留的片段为 `None`
+    pub fn scope(&self) -> Option<Scope> {
+        match self.kind {
+            SegmentKind::Code | SegmentKind::Tag { .. } | SegmentKind::InlineCode => None,
+            SegmentKind::Comment { style: CommentStyle::Doc } | SegmentKind::DocMarkdown => Some(Scope::DocComments),
+            SegmentKind::Comment { .. } => Some(Scope::Comments),
+            SegmentKind::StringLiteral { .. } => Some(Scope::Strings),
+            SegmentKind::Text => Some(Scope::Text),
+        }
+    }
+}
+
+/// 按指定格式切分 `input`，片段按顺序首尾相接，覆盖整个输入
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::{CommentStyle, Format, SegmentKind, segments};
+///
+/// let input = "let s = \"你好\"; // 注释";
+/// let kinds: Vec<_> = segments(input, Format::Js)
+///     .unwrap()
+///     .into_iter()
+///     .map(|segment| (segment.kind, &input[segment.range]))
+///     .collect();
+/// assert_eq!(
+///     kinds,
+///     [
+///         (SegmentKind::Code, "let s = \""),
+///         (SegmentKind::StringLiteral { quote: Some('"') }, "你好"),
+///         (SegmentKind::Code, "\"; //"),
+///         (SegmentKind::Comment { style: CommentStyle::Line }, " 注释"),
+///     ]
+/// );
+/// ```
+pub fn segments(input: &str, format: Format) -> Result<Vec<Segment>> {
+    format.segment(input)
+}
+
+/// Collects the segments of an input in order, filling the gaps between the
+/// pushed ranges with `Code` and joining adjacent segments of the same kind.
+pub(crate) struct Segments {
+    segments: Vec<Segment>,
+    end: usize,
+}
+
+impl Segments {
+    pub(crate) fn new() -> Self {
+        Self { segments: Vec::new(), end: 0 }
+    }
+
+    pub(crate) fn push(&mut self, kind: SegmentKind, range: Range<usize>) {
+        self.push_segment(Segment::new(kind, range));
+    }
+
+    pub(crate) fn push_segment(&mut self, segment: Segment) {
+        if segment.range.is_empty() {
+            return;
+        }
+        debug_assert!(segment.range.start >= self.end, "segments out of order");
+        if segment.range.start > self.end {
+            self.push_code(self.end..segment.range.start);
+        }
+        self.end = segment.range.end;
+        match self.segments.last_mut() {
+            // Lexers may push a run piece by piece
+            Some(last) if last.range.end == segment.range.start && (last.kind, last.markup) == (segment.kind, segment.markup) => {
+                last.range.end = segment.range.end
+            }
+            _ => self.segments.push(segment),
+        }
+    }
+
+    fn push_code(&mut self, range: Range<usize>) {
+        self.push_segment(Segment::new(SegmentKind::Code, range));
+    }
+
+    /// Adds the segments of a sub-input that starts at `offset`. With `kind`,
+    /// everything that gets spaced counts as that kind, e.g. the text of markup
+    /// in a comment.
+    pub(crate) fn extend(&mut self, offset: usize, segments: Vec<Segment>, kind: Option<SegmentKind>) {
+        for mut segment in segments {
+            segment.range = segment.range.start + offset..segment.range.end + offset;
+            if let Some(kind) = kind
+                && segment.scope().is_some()
+            {
+                segment.kind = kind;
+            }
+            self.push_segment(segment);
+        }
+    }
+
+    /// The end of the last pushed segment.
+    pub(crate) fn end(&self) -> usize {
+        self.end
+    }
+
+    pub(crate) fn finish(mut self, len: usize) -> Vec<Segment> {
+        if len > self.end {
+            self.push_code(self.end..len);
+        }
+        self.segments
+    }
+}
+
+/// Writes `input` with every segment but code spaced according to its scope.
+pub(crate) fn apply(input: &str, segments: &[Segment], ctx: &mut Context) -> String {
+    let mut pieces: Vec<Cow<str>> = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let text = &input[segment.range.clone()];
+        let start = segment.range.start;
+        let piece = match (segment.kind, segment.scope()) {
+            (_, None) => Cow::Borrowed(text),
+            (SegmentKind::DocMarkdown, Some(scope)) => {
+                if !ctx.in_scope(scope) || ctx.is_ignored(segment.range.clone()) {
+                    Cow::Borrowed(text)
+                } else {
+                    Cow::Owned(ctx.nested(start, |ctx| ctx.scoped(scope, |ctx| rust::doc_markdown(text, ctx))))
+                }
+            }
+            (_, Some(scope)) => match segment.markup {
+                Markup::Plain => ctx.spacing(scope, text, start),
+                Markup::Html { tags } => ctx.html_spacing(scope, text, start, tags),
+            },
+        };
+        pieces.push(piece);
+    }
+    if !ctx.options.is_unspacing() {
+        join(input, segments, &mut pieces, ctx);
+    }
+    pieces.concat()
+}
+
+/// Adds the spacers that each segment, spaced on its own, misses at inline
+/// markup between two runs of text, e.g. `中文<a>Link</a>`.
+fn join(input: &str, segments: &[Segment], pieces: &mut [Cow<str>], ctx: &mut Context) {
+    let options = ctx.options;
+    // The last run of text, which the next one joins if only inline markup comes between
+    let mut last = None;
+    for (j, segment) in segments.iter().enumerate() {
+        match segment.kind {
+            SegmentKind::Tag { element, .. } if !options.is_separator(element) => continue,
+            // Attribute values and the like are not next to the text around the tag
+            _ if segment.markup == (Markup::Html { tags: false }) => continue,
+            SegmentKind::Text | SegmentKind::Comment { .. } | SegmentKind::StringLiteral { .. } | SegmentKind::InlineCode => {}
+            _ => {
+                last = None;
+                continue;
+            }
+        }
+        if let Some(i) = last
+            && j > i + 1
+        {
+            join_at(input, segments, i, j, pieces, ctx);
+        }
+        last = Some(j);
+    }
+}
+
+/// Inserts a spacer between the text of segments `i` and `j` if a rule calls
+/// for one, outside the closing markup of `i` and the opening markup of `j`.
+fn join_at(input: &str, segments: &[Segment], i: usize, j: usize, pieces: &mut [Cow<str>], ctx: &mut Context) {
+    let (a, b) = (&segments[i], &segments[j]);
+    if [a, b].iter().any(|segment| segment.scope().is_some_and(|scope| !ctx.in_scope(scope)))
+        || ctx.is_ignored(a.range.end - 1..b.range.start + 1)
+    {
+        return;
+    }
+    let Some(prev) = input[a.range.clone()].chars().next_back() else {
+        return;
+    };
+    // The spacer goes next to the tags, so it is written like the text around them
+    let markup = if b.kind == SegmentKind::InlineCode { a.markup } else { b.markup };
+    let spacer = match markup {
+        Markup::Plain => ctx.options.spacer().as_str(),
+        Markup::Html { tags } => ctx.options.spacer().html(tags),
+    };
+    // A spacer made of tags, e.g. an empty `<span>`, may already be there
+    let tags = &segments[i + 1..j];
+    if (0..tags.len()).any(|k1| (k1..tags.len()).any(|k2| input[tags[k1].range.start..tags[k2].range.end] == *spacer)) {
+        return;
+    }
+    let scope = b.scope().or(a.scope()).unwrap_or(Scope::Text);
+    let Some(rule) = ctx.joins(scope, prev, &input[b.range.clone()], spacer) else {
+        return;
+    };
+    let k = (i + 1..j)
+        .take_while(|&k| matches!(segments[k].kind, SegmentKind::Tag { open: false, .. }))
+        .last()
+        .unwrap_or(i);
+    pieces[k].to_mut().push_str(spacer);
+    ctx.insert(segments[k].range.end, spacer, rule);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str, format: Format) -> Vec<(SegmentKind, &str)> {
+        segments(input, format)
+            .unwrap()
+            .into_iter()
+            .map(|segment| (segment.kind, &input[segment.range]))
+            .collect()
+    }
+
+    #[test]
+    fn test_segments_cover_input() {
+        let cases = [
+            (Format::Text, "中文English"),
+            (Format::Html, "<p title=\"标题\">段落&amp;文字</p><!-- <b>注释</b> --><script>// 脚本\n</script>"),
+            (Format::Markdown, "# 标题\n\n段落`code`文字\n\n<!-- 注释 -->\n"),
+            (Format::Css, "a { content: '内容'; } /* 注释 */"),
+            (Format::Js, "let a = `模板${x}文字`; /** 文档 */"),
+            (Format::Rust, "/// 文档\nfn main() { let s = r#\"字符串\"#; } // 注释"),
+            (Format::Json, r#"{"a": ["乙"]}"#),
+            (Format::Json5, "{ a: '甲', // 注释\n}"),
+            (Format::Php, "<p>段落</p><?php echo \"$x字符串\"; # 注释 ?>"),
+        ];
+        for (format, input) in cases {
+            let segments = segments(input, format).unwrap();
+            let mut end = 0;
+            for segment in &segments {
+                assert_eq!(segment.range.start, end, "{:?}", format);
+                assert!(!segment.range.is_empty(), "{:?}", format);
+                end = segment.range.end;
+            }
+            assert_eq!(end, input.len(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_kinds() {
+        let p = |open| SegmentKind::Tag { element: Element::Block, open };
+        let b = |open| SegmentKind::Tag { element: Element::Inline, open };
+        assert_eq!(
+            kinds("/// 文档\nfn f() {} /* 块 */", Format::Rust),
+            [
+                (SegmentKind::DocMarkdown, "/// 文档\n"),
+                (SegmentKind::Code, "fn f() {} /*"),
+                (SegmentKind::Comment { style: CommentStyle::Block }, " 块 "),
+                (SegmentKind::Code, "*/"),
+            ]
+        );
+        assert_eq!(
+            kinds("<p title='标题'>段落&amp;文字</p><!--<b>注释</b>-->", Format::Html),
+            [
+                (p(true), "<p title='"),
+                (SegmentKind::Text, "标题"),
+                (p(true), "'>"),
+                (SegmentKind::Text, "段落"),
+                (SegmentKind::Code, "&amp;"),
+                (SegmentKind::Text, "文字"),
+                (p(false), "</p>"),
+                (SegmentKind::Code, "<!--"),
+                (b(true), "<b>"),
+                (SegmentKind::Comment { style: CommentStyle::Html }, "注释"),
+                (b(false), "</b>"),
+                (SegmentKind::Code, "-->"),
+            ]
+        );
+        assert_eq!(
+            kinds("a = `模板${x}文字`", Format::Js),
+            [
+                (SegmentKind::Code, "a = `"),
+                (SegmentKind::StringLiteral { quote: Some('`') }, "模板"),
+                (SegmentKind::Code, "${x}"),
+                (SegmentKind::StringLiteral { quote: Some('`') }, "文字"),
+                (SegmentKind::Code, "`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markup() {
+        let input = "<title>标题</title><p title=\"提示\">段落</p>";
+        let markup: Vec<_> = segments(input, Format::Html)
+            .unwrap()
+            .into_iter()
+            .filter(|segment| segment.kind == SegmentKind::Text)
+            .map(|segment| (&input[segment.range], segment.markup))
+            .collect();
+        assert_eq!(
+            markup,
+            [
+                ("标题", Markup::Html { tags: false }),
+                ("提示", Markup::Html { tags: false }),
+                ("段落", Markup::Html { tags: true }),
+            ]
+        );
+    }
+}
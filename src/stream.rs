@@ -0,0 +1,170 @@
+use std::io::{self, Read, Write};
+
+use crate::{DEFAULT_OPTIONS, Scope, SpacingOptions, transform};
+
+/// Bytes read at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A line without a line break is cut at whitespace once it grows this long.
+const MAX_PENDING: usize = 1024 * 1024;
+
+/// 流式处理纯文本：从 `reader` 逐块读入，加好空格后写入 `writer`
+///
+/// 按行处理，内存占用取决于最长的一行而不是输入的总长，超过 1 MiB 的行在空白处切开。
+/// 结果与对全文调用 [`spacing`](crate::spacing) 相同；输入不是合法的 UTF-8 时返回 `InvalidData` 错误。
+///
+/// # Examples
+///
+/// ```
+/// use paranoid_space::spacing_stream;
+///
+/// let mut output = Vec::new();
+/// spacing_stream("当你凝视着bug，\nbug也凝视着你".as_bytes(), &mut output).unwrap();
+/// assert_eq!(String::from_utf8(output).unwrap(), "当你凝视着 bug，\nbug 也凝视着你");
+/// ```
+pub fn spacing_stream(reader: impl Read, writer: impl Write) -> io::Result<()> {
+    spacing_stream_with_options(reader, writer, &DEFAULT_OPTIONS)
+}
+
+/// 按照给定的规则配置流式处理，见 [`spacing_stream`]
+pub fn spacing_stream_with_options(reader: impl Read, writer: impl Write, options: &SpacingOptions) -> io::Result<()> {
+    stream(reader, writer, options, MAX_PENDING)
+}
+
+fn stream(mut reader: impl Read, mut writer: impl Write, options: &SpacingOptions, max_pending: usize) -> io::Result<()> {
+    let mut pending: Vec<u8> = Vec::new();
+    // The last character written, which the next piece is processed after
+    let mut context = String::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        pending.extend_from_slice(&chunk[..read]);
+        // A character split across reads waits for the rest of its bytes
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let text = std::str::from_utf8(&pending[..valid]).expect("checked above");
+        let cut = cut_point(text, max_pending);
+        if cut > 0 {
+            write_piece(&mut writer, &mut context, &text[..cut], options)?;
+            pending.drain(..cut);
+        }
+    }
+    let text = std::str::from_utf8(&pending).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write_piece(&mut writer, &mut context, text, options)?;
+    writer.flush()
+}
+
+/// Where the text read so far can be cut: after the last line break or, in a
+/// line longer than `max_pending`, before the last run of whitespace. No rule
+/// looks past the line, or the word for a long line, so each piece comes out
+/// as it would in the whole text. A word as long as `max_pending` is cut
+/// anywhere, and a token or exception across the cut is then missed.
+fn cut_point(text: &str, max_pending: usize) -> usize {
+    if let Some(i) = text.rfind('\n') {
+        return i + 1;
+    }
+    if text.len() < max_pending {
+        return 0;
+    }
+    let before_run = text
+        .trim_end_matches(|c: char| !c.is_whitespace())
+        .trim_end_matches(char::is_whitespace);
+    if !before_run.is_empty() {
+        return before_run.len();
+    }
+    match text.char_indices().next_back() {
+        Some((last, _)) if last > 0 => last,
+        _ => text.len(),
+    }
+}
+
+/// Writes `piece` processed after `context`, the character written before it,
+/// and keeps the last character of `piece` as the next context.
+fn write_piece(writer: &mut impl Write, context: &mut String, piece: &str, options: &SpacingOptions) -> io::Result<()> {
+    if piece.is_empty() {
+        return Ok(());
+    }
+    let text = format!("{}{}", context, piece);
+    // The context was written with the previous piece, possibly converted
+    let mut written = context.len();
+    let result = transform(&text, options, Scope::Text, options.spacer().as_str(), &[], |range, inserted, _| {
+        if range.start < context.len() {
+            written = written + inserted.len() - range.len();
+        }
+    });
+    writer.write_all(result[written..].as_bytes())?;
+    *context = piece.chars().next_back().map(String::from).unwrap_or_default();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SpacingRule, spacing_with_options};
+
+    /// Hands out a few bytes per read, splitting characters apart
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.0.len().min(buf.len()).min(1 + self.0.len() % 3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    fn streamed(input: &str, options: &SpacingOptions, max_pending: usize) -> String {
+        let mut output = Vec::new();
+        stream(Trickle(input.as_bytes()), &mut output, options, max_pending).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_stream() {
+        let input = std::fs::read_to_string("test-data/test.txt").unwrap()
+            + "\n中文  English，价格$5,行首\n数字１２３(注释)，see https://例子.com/路径 吧 中文 English";
+        let options = [
+            SpacingOptions::new(),
+            SpacingOptions::new().disable(SpacingRule::LineStart),
+            SpacingOptions::new()
+                .enable(SpacingRule::NormalizeSpaces)
+                .enable(SpacingRule::FullWidthPunctuation)
+                .enable(SpacingRule::HalfWidthAlphanumerics),
+            SpacingOptions::new().with_unspacing(true),
+        ];
+        for options in &options {
+            let expected = spacing_with_options(&input, options);
+            assert_eq!(streamed(&input, options, MAX_PENDING), expected);
+            // Long lines are cut at whitespace
+            assert_eq!(streamed(&input, options, 64), expected);
+        }
+    }
+
+    #[test]
+    fn test_cut_point() {
+        assert_eq!(cut_point("第一行\n第二", 100), "第一行\n".len());
+        assert_eq!(cut_point("没有换行 abc", 100), 0);
+        assert_eq!(cut_point("没有换行  abc", 8), "没有换行".len());
+        assert_eq!(cut_point("没有空白", 8), "没有空".len());
+        assert_eq!(cut_point("  没有空白", 8), "  没有空".len());
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut output = Vec::new();
+        let error = spacing_stream(&b"abc\xff\xfe"[..], &mut output).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // A truncated character at the end
+        let error = spacing_stream(&"中文".as_bytes()[..5], &mut output).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::CharClass;
+
+/// Characters that end a token besides whitespace: quotes, angle brackets and
+/// full-width punctuation.
+const STOP: &str = r#"\s<>"'`，。！？；：、（）【】《》「」『』“”‘’"#;
+
+/// Token patterns by priority, each with its path separator if it is a file
+/// path; a token overlapping an earlier one is dropped.
+static PATTERNS: LazyLock<Vec<(Regex, Option<char>)>> = LazyLock::new(|| {
+    [
+        // URL, including IDN hosts and CJK path segments
+        (format!(r"[A-Za-z][A-Za-z0-9+.-]*://[^{STOP}]+"), None),
+        // Email address with an ASCII local part, so `联系user@公司.cn` still splits;
+        // an ASCII TLD is preferred, so that `.cn获取` ends at `cn`, but IDN
+        // TLDs like `.中国` are allowed
+        (r"[A-Za-z0-9._%+-]+@(?:[\p{L}\p{N}-]+\.)+(?:[A-Za-z]{2,}|\p{L}{2,})".to_string(), None),
+        // Windows path
+        (format!(r"[A-Za-z]:\\[^{STOP}|*?]*"), Some('\\')),
+        // Unix path: absolute with two or more segments, or relative to `~` or `.`
+        (format!(r"(?:(?:~|\.\.?)(?:/[^{STOP}/]+)+|(?:/[^{STOP}/]+){{2,}})/?"), Some('/')),
+        // Semantic version
+        (r"v?\d+\.\d+\.\d+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?".to_string(), None),
+        // Mention and hashtag, optionally closed as in `#话题#`
+        (r"@[\p{L}\p{N}_-]+".to_string(), None),
+        (r"#[\p{L}\p{N}_-]+#?".to_string(), None),
+    ]
+    .into_iter()
+    .map(|(pattern, separator)| (Regex::new(&pattern).expect("invalid token pattern"), separator))
+    .collect()
+});
+
+/// Byte ranges of URLs, emails, paths, versions, mentions and hashtags in
+/// `text`, sorted by start.
+pub(crate) fn protected_ranges(text: &str) -> Vec<Range<usize>> {
+    // Start to end, so that overlaps are found without a scan
+    let mut ranges: BTreeMap<usize, usize> = BTreeMap::new();
+    for (pattern, separator) in PATTERNS.iter() {
+        for m in pattern.find_iter(text) {
+            // A token starts a word: `and/or/but` is not a path, nor `C#` a hashtag
+            if text[..m.start()]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                continue;
+            }
+            // Sentence punctuation right after a token is not part of it, nor a
+            // closing bracket unless it closes one in the token: `a.b/c(d)`
+            let mut token = m.as_str();
+            while let Some(c) = token.chars().next_back() {
+                let open = match c {
+                    '.' | ',' | ';' | ':' | '!' | '?' => None,
+                    ')' => Some('('),
+                    ']' => Some('['),
+                    '}' => Some('{'),
+                    _ => break,
+                };
+                if open.is_some_and(|open| token.matches(open).count() >= token.matches(c).count()) {
+                    break;
+                }
+                token = &token[..token.len() - 1];
+            }
+            // Prose may follow a path directly. Where the script changes, the
+            // CJK part is prose: in a last segment that starts in Latin script,
+            // as in `~/Documents目录`, or in one after a Latin-only path, as in
+            // `/usr/local/bin/目录`. CJK names such as `~/配置/文件` stay whole.
+            if let Some(separator) = separator {
+                let last = token.rfind(*separator).map_or(0, |i| i + 1);
+                let is_cjk = |c: char| CharClass::of(c).is_cjk();
+                let (path, segment) = token.split_at(last);
+                let cut = match segment.chars().next() {
+                    Some(c) if !is_cjk(c) => segment.find(is_cjk),
+                    Some(_) if path.chars().any(char::is_alphanumeric) && !path.chars().any(is_cjk) => Some(0),
+                    _ => None,
+                };
+                if let Some(i) = cut {
+                    token = &token[..last + i];
+                }
+            }
+            let range = m.start()..m.start() + token.len();
+            // The ranges are disjoint, so only the last one starting before
+            // this one ends may overlap it
+            let overlaps = ranges
+                .range(..range.end)
+                .next_back()
+                .is_some_and(|(_, &end)| range.start < end);
+            if range.len() > 1 && !overlaps {
+                ranges.insert(range.start, range.end);
+            }
+        }
+    }
+    ranges.into_iter().map(|(start, end)| start..end).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<&str> {
+        protected_ranges(text).into_iter().map(|range| &text[range]).collect()
+    }
+
+    #[test]
+    fn test_protected_ranges() {
+        assert_eq!(
+            tokens("访问https://例子.com/路径?q=中文，或者http://a.b/c."),
+            ["https://例子.com/路径?q=中文", "http://a.b/c"]
+        );
+        assert_eq!(tokens("联系user@公司.cn获取"), ["user@公司.cn"]);
+        assert_eq!(tokens("邮件user@公司.中国"), ["user@公司.中国"]);
+        assert_eq!(tokens("（见https://a.b/c_(d)）和(https://a.b/e)"), ["https://a.b/c_(d)", "https://a.b/e"]);
+        assert_eq!(tokens(r"打开C:\用户\文档 和~/配置/文件"), [r"C:\用户\文档", "~/配置/文件"]);
+        assert_eq!(tokens("路径/usr/本地/bin，不是and/or"), ["/usr/本地/bin"]);
+        assert_eq!(tokens("文件在/usr/本地/库目录"), ["/usr/本地/库目录"]);
+        assert_eq!(tokens("~/Documents目录 /usr/bin/目录"), ["~/Documents", "/usr/bin/"]);
+        assert_eq!(tokens("见/home/用户/文档 和~/文件"), ["/home/用户/文档", "~/文件"]);
+        assert_eq!(tokens("v1.2.3版本和2.0.0-rc.1"), ["v1.2.3", "2.0.0-rc.1"]);
+        assert_eq!(tokens("@张三 发了#话题# #标签Tag 用C#写"), ["@张三", "#话题#", "#标签Tag"]);
+        assert!(tokens("普通文本plain text 1.2").is_empty());
+    }
+}
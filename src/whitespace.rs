@@ -0,0 +1,77 @@
+use std::ops::Range;
+
+use crate::CharClass;
+
+/// Horizontal whitespace: tabs and space separators, including the
+/// ideographic space and NBSP, but not line breaks.
+pub(crate) fn is_blank(c: char) -> bool {
+    // General category Zs, spelled out as it is checked for every character
+    matches!(
+        c,
+        ' ' | '\t' | '\u{a0}' | '\u{1680}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{205f}' | '\u{3000}'
+    )
+}
+
+/// Byte ranges of the whitespace between CJK and Latin text, sorted by start.
+/// Callers skip the runs that already are the spacer.
+///
+/// Whitespace at the start or end of a line is left out, which keeps
+/// indentation and Markdown hard breaks. With `keep_alignment`, as in code
+/// comments, only a single ideographic space or NBSP counts; longer runs and
+/// tabs are taken as alignment.
+pub(crate) fn boundary_runs(text: &str, keep_alignment: bool) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut prev: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if !is_blank(c) {
+            prev = Some(c);
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some((offset, c)) = chars.next_if(|&(_, c)| is_blank(c)) {
+            end = offset + c.len_utf8();
+        }
+        let next = chars.peek().map(|&(_, c)| c);
+        let (Some(before), Some(after)) = (prev, next) else {
+            continue;
+        };
+        let (before, after) = (CharClass::of(before), CharClass::of(after));
+        let run = &text[start..end];
+        if before != CharClass::Space
+            && after != CharClass::Space
+            && (before.is_cjk() && after.is_western() || before.is_western() && after.is_cjk())
+            && !(keep_alignment && (run.chars().count() > 1 || run == "\t"))
+        {
+            runs.push(start..end);
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
+
+    #[test]
+    fn test_is_blank() {
+        for c in '\0'..=char::MAX {
+            assert_eq!(is_blank(c), c == '\t' || c.general_category() == GeneralCategory::SpaceSeparator, "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn test_boundary_runs() {
+        let text = "中文  English\t中文\u{3000}A\u{a0}中 English　中";
+        let runs: Vec<&str> = boundary_runs(text, false).into_iter().map(|range| &text[range]).collect();
+        assert_eq!(runs, ["  ", "\t", "\u{3000}", "\u{a0}", " ", "　"]);
+
+        // Indentation, trailing spaces and whitespace within a script stay
+        assert!(boundary_runs("  中文English  \n中文  中文\nab  cd", false).is_empty());
+
+        let text = "名称    Name\t中文　A";
+        let runs: Vec<&str> = boundary_runs(text, true).into_iter().map(|range| &text[range]).collect();
+        assert_eq!(runs, ["　"]);
+    }
+}
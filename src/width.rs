@@ -0,0 +1,118 @@
+use crate::CharClass;
+
+/// Full-width form of each half-width punctuation mark that may be converted.
+fn full_width(c: char) -> Option<char> {
+    match c {
+        ',' => Some('，'),
+        '.' => Some('。'),
+        '!' => Some('！'),
+        '?' => Some('？'),
+        ':' => Some('：'),
+        ';' => Some('；'),
+        '(' => Some('（'),
+        ')' => Some('）'),
+        _ => None,
+    }
+}
+
+/// Chinese and Japanese text; Korean uses half-width punctuation itself.
+fn is_cjk(c: char) -> bool {
+    let class = CharClass::of(c);
+    class.is_cjk() && class != CharClass::Hangul
+}
+
+/// Half-width punctuation in `text` that belongs to the CJK text around it,
+/// as byte offsets with the full-width replacements, sorted by offset.
+///
+/// `, ! ? : ;` are converted right after CJK text, `.` only between CJK
+/// characters, so that sentence ends, decimals and ellipses stay as they are,
+/// and `( )` in pairs right after CJK text and enclosing some. A mark followed
+/// by other ASCII punctuation, as in `?.` or `!=`, looks like code and is kept.
+pub(crate) fn full_width_punctuation(text: &str) -> Vec<(usize, char)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut conversions = Vec::new();
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| chars[i].1);
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        if !prev.is_some_and(is_cjk) {
+            continue;
+        }
+        let code_like = next.is_some_and(|next| next.is_ascii_punctuation() && !"\"')]}".contains(next));
+        match c {
+            ',' | '!' | '?' | ':' | ';' if !code_like => conversions.push((offset, full_width(c).unwrap())),
+            '.' if next.is_some_and(is_cjk) => conversions.push((offset, full_width(c).unwrap())),
+            '(' => {
+                let rest = &chars[i + 1..];
+                let Some(close) = rest.iter().position(|&(_, c)| matches!(c, '(' | ')' | '\n')) else {
+                    continue;
+                };
+                if rest[close].1 == ')' && rest[..close].iter().any(|&(_, c)| is_cjk(c)) {
+                    conversions.push((offset, '（'));
+                    conversions.push((rest[close].0, '）'));
+                }
+            }
+            _ => {}
+        }
+    }
+    conversions.sort_by_key(|&(offset, _)| offset);
+    conversions
+}
+
+/// Half-width form of a full-width letter, digit or one of `％＋＝＠`. Other
+/// full-width ASCII variants are punctuation kept in CJK text, or would change
+/// the meaning of the code or markup around them, like `＂` in a string.
+fn half_width(c: char) -> Option<char> {
+    match c {
+        'Ａ'..='Ｚ' | 'ａ'..='ｚ' | '０'..='９' | '％' | '＋' | '＝' | '＠' => {
+            char::from_u32(c as u32 - 0xFEE0)
+        }
+        _ => None,
+    }
+}
+
+/// Full-width letters and digits in `text`, as byte offsets with the
+/// half-width replacements.
+pub(crate) fn half_width_alphanumerics(text: &str) -> Vec<(usize, char)> {
+    text.char_indices()
+        .filter_map(|(offset, c)| half_width(c).map(|half| (offset, half)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(text: &str) -> String {
+        let mut result = text.to_string();
+        for (offset, c) in full_width_punctuation(text).into_iter().rev() {
+            result.replace_range(offset..offset + 1, c.encode_utf8(&mut [0; 4]));
+        }
+        result
+    }
+
+    #[test]
+    fn test_full_width_punctuation() {
+        assert_eq!(convert("你好,世界!真的吗?是的:好;"), "你好，世界！真的吗？是的：好；");
+        assert_eq!(convert("第一句.第二句."), "第一句。第二句.");
+        assert_eq!(convert("参数(可选)和函数(a, b)"), "参数（可选）和函数(a, b)");
+        assert_eq!(convert("中文,\"引号\""), "中文，\"引号\"");
+        assert_eq!(convert("圆周率3.14,省略..."), "圆周率3.14,省略...");
+        assert_eq!(convert("可选链?.和不等!="), "可选链?.和不等!=");
+        assert_eq!(convert("English, text. 한국어, 문장."), "English, text. 한국어, 문장.");
+        assert_eq!(convert("「引用」,接着"), "「引用」，接着");
+    }
+
+    #[test]
+    fn test_half_width_alphanumerics() {
+        let convert = |text: &str| {
+            let mut result = text.to_string();
+            for (offset, c) in half_width_alphanumerics(text).into_iter().rev() {
+                let len = result[offset..].chars().next().unwrap().len_utf8();
+                result.replace_range(offset..offset + len, c.encode_utf8(&mut [0; 4]));
+            }
+            result
+        };
+        assert_eq!(convert("ＡＢＣａｂｃ０１２９％＋＝＠"), "ABCabc0129%+=@");
+        assert_eq!(convert("，．！？（）＂＇＼＄＜＆＊＃"), "，．！？（）＂＇＼＄＜＆＊＃");
+    }
+}